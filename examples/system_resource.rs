@@ -0,0 +1,39 @@
+use clap::Parser;
+use encoding_rs::mem::encode_latin1_lossy;
+use mikrotik_api::{prelude::MikrotikDevice, simple::SimpleResult};
+use std::net::IpAddr;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// device to contact
+    device: IpAddr,
+
+    /// login password
+    #[arg(short, long)]
+    password: Option<Box<str>>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let device: MikrotikDevice<SimpleResult> = MikrotikDevice::connect(
+        (args.device, 8728),
+        b"admin",
+        args.password.as_deref().map(|v| encode_latin1_lossy(v)),
+    )
+    .await?;
+    let resource = device.system_resource().await?;
+    println!(
+        "{} {} running {} for {}",
+        resource.platform,
+        resource.board_name,
+        resource.version_number(),
+        resource.uptime
+    );
+    println!(
+        "cpu load {}%, memory {}/{} bytes free",
+        resource.cpu_load, resource.free_memory, resource.total_memory
+    );
+    Ok(())
+}
@@ -28,7 +28,7 @@ async fn main() -> anyhow::Result<()> {
     .await?;
     let mut stream = device
         .send_simple_command(b"/system/resource/print", ())
-        .await;
+        .await?;
     while let Some(result) = stream.next().await {
         println!("Result: {result:?}");
     }
@@ -0,0 +1,181 @@
+//! Derive macro for `mikrotik_api::resource::RouterOsResource`, see the documentation there.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{ext::IdentExt, parse_macro_input, Data, DeriveInput, Error, Fields, LitByteStr, LitStr};
+
+/// Implements `RouterOsResource` and `ReadableResource` for a struct with named fields.
+///
+/// The menu is given by `#[ros(path = "/ip/address")]` on the struct. Each field maps to the
+/// attribute of its name with `_` replaced by `-`, a field `id` to `.id`. Fields accept:
+///
+/// * `#[ros(rename = "name")]` - name of the attribute
+/// * `#[ros(read_only)]` - only read from replies, never written by `add` or `set`
+/// * `#[ros(skip)]` - not an attribute, filled with `Default::default()`
+///
+/// The struct is a single item, not a reply of a device, so no `ParsedMessage` is derived:
+/// a device streaming the items of a command uses `Resource<T>` of the `resource` module
+/// as message, which adds the traps, errors and `!done` of the command.
+#[proc_macro_derive(RouterOsResource, attributes(ros))]
+pub fn derive_router_os_resource(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct ResourceField {
+    ident: syn::Ident,
+    attribute: String,
+    read_only: bool,
+    skip: bool,
+}
+
+impl ResourceField {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| Error::new_spanned(field, "field must be named"))?;
+        let mut rename = None;
+        let mut read_only = false;
+        let mut skip = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("ros"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("read_only") {
+                    read_only = true;
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else {
+                    return Err(meta.error("expected `rename`, `read_only` or `skip`"));
+                }
+                Ok(())
+            })?;
+        }
+        let attribute = match rename {
+            Some(rename) => rename,
+            None => match ident.unraw().to_string().replace('_', "-") {
+                name if name == "id" => ".id".to_string(),
+                name => name,
+            },
+        };
+        // The id selects the item, it is never written as value
+        let read_only = read_only || attribute == ".id";
+        Ok(Self {
+            ident,
+            attribute,
+            read_only,
+            skip,
+        })
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut path = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("ros"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("path") {
+                path = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `path`"))
+            }
+        })?;
+    }
+    let path = path.ok_or_else(|| {
+        Error::new_spanned(
+            &input.ident,
+            "missing menu path, add #[ros(path = \"...\")]",
+        )
+    })?;
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "RouterOsResource can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "RouterOsResource requires named fields",
+        ));
+    };
+    let fields = fields
+        .named
+        .iter()
+        .map(ResourceField::parse)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let attributes: Vec<_> = fields.iter().filter(|field| !field.skip).collect();
+    let count = attributes.len();
+    let properties = attributes.iter().map(|field| &field.attribute);
+    let keys = attributes
+        .iter()
+        .map(|field| LitByteStr::new(field.attribute.as_bytes(), field.ident.span()));
+    let indices = 0..count;
+    let initializers = fields.iter().map(|field| {
+        let ident = &field.ident;
+        match attributes
+            .iter()
+            .position(|attribute| attribute.ident == field.ident)
+        {
+            Some(index) => {
+                let attribute = &field.attribute;
+                quote! {
+                    #ident: ::mikrotik_api::resource::parse_attribute(#attribute, values[#index])?
+                }
+            }
+            None => quote! { #ident: ::core::default::Default::default() },
+        }
+    });
+    let writes = attributes
+        .iter()
+        .filter(|field| !field.read_only)
+        .map(|field| {
+            let ident = &field.ident;
+            let key = LitByteStr::new(field.attribute.as_bytes(), ident.span());
+            quote! { .value_attribute(#key, &self.#ident) }
+        });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mikrotik_api::resource::ReadableResource for #name #type_generics #where_clause {
+            const PATH: &'static str = #path;
+            const PROPERTIES: &'static [&'static str] = &[#(#properties),*];
+
+            fn from_sentence(
+                sentence: &[(&[u8], ::core::option::Option<&[u8]>)],
+            ) -> ::core::result::Result<Self, ::mikrotik_api::resource::ResourceError> {
+                let mut values: [::core::option::Option<::core::option::Option<&[u8]>>; #count] =
+                    [::core::option::Option::None; #count];
+                for (key, value) in sentence {
+                    match *key {
+                        #(#keys => values[#indices] = ::core::option::Option::Some(*value),)*
+                        _ => {}
+                    }
+                }
+                ::core::result::Result::Ok(Self {
+                    #(#initializers,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::mikrotik_api::resource::RouterOsResource for #name #type_generics #where_clause {
+            fn write_attributes(
+                &self,
+                builder: ::mikrotik_api::prelude::CommandBuilder,
+            ) -> ::mikrotik_api::prelude::CommandBuilder {
+                builder #(#writes)*
+            }
+        }
+    })
+}
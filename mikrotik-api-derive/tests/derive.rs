@@ -0,0 +1,99 @@
+use mikrotik_api::{
+    prelude::CommandBuilder,
+    resource::{ReadableResource, ResourceError, RouterOsResource},
+    values::ItemId,
+};
+
+#[derive(Debug, PartialEq, RouterOsResource)]
+#[ros(path = "/interface/vlan")]
+struct Vlan {
+    id: Option<ItemId>,
+    name: String,
+    vlan_id: u16,
+    #[ros(rename = "interface")]
+    parent: String,
+    #[ros(read_only)]
+    running: Option<bool>,
+    #[ros(skip)]
+    seen: u32,
+    r#type: Option<String>,
+}
+
+/// Splits the encoded words of a command, all test words are shorter than 128 bytes
+fn words(data: &[u8]) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut rest = data;
+    while let [len, tail @ ..] = rest {
+        let (word, tail) = tail.split_at(usize::from(*len));
+        if !word.is_empty() {
+            words.push(std::str::from_utf8(word).unwrap());
+        }
+        rest = tail;
+    }
+    words
+}
+
+#[test]
+fn test_properties() {
+    assert_eq!(Vlan::PATH, "/interface/vlan");
+    assert_eq!(
+        Vlan::PROPERTIES,
+        [".id", "name", "vlan-id", "interface", "running", "type"]
+    );
+}
+
+#[test]
+fn test_from_sentence() {
+    let vlan = Vlan::from_sentence(&[
+        (b".id", Some(b"*5")),
+        (b"name", Some(b"vlan10")),
+        (b"vlan-id", Some(b"10")),
+        (b"interface", Some(b"bridge")),
+        (b"running", Some(b"true")),
+        (b"seen", Some(b"3")),
+    ])
+    .unwrap();
+    assert_eq!(
+        vlan,
+        Vlan {
+            id: Some(ItemId(5)),
+            name: "vlan10".to_string(),
+            vlan_id: 10,
+            parent: "bridge".to_string(),
+            running: Some(true),
+            seen: 0,
+            r#type: None,
+        }
+    );
+    assert_eq!(
+        Vlan::from_sentence(&[(b"name", Some(b"vlan10")), (b"vlan-id", Some(b"10"))]),
+        Err(ResourceError::Missing("interface"))
+    );
+}
+
+#[test]
+fn test_write_attributes() {
+    let vlan = Vlan {
+        id: Some(ItemId(5)),
+        name: "vlan10".to_string(),
+        vlan_id: 10,
+        parent: "bridge".to_string(),
+        running: Some(true),
+        seen: 3,
+        r#type: Some("vlan".to_string()),
+    };
+    let command = vlan
+        .write_attributes(CommandBuilder::new(1, b"/interface/vlan/set"))
+        .build();
+    assert_eq!(
+        words(&command.data),
+        [
+            "/interface/vlan/set",
+            ".tag=1",
+            "=name=vlan10",
+            "=vlan-id=10",
+            "=interface=bridge",
+            "=type=vlan"
+        ]
+    );
+}
@@ -0,0 +1,411 @@
+//! Deserialize the attributes of a reply sentence with serde.
+//!
+//! The attributes of a sentence are presented as a map. RouterOS conventions are applied:
+//!
+//! * the key `.id` is deserialized into a field `id`, other keys keep their leading `.`, so a field
+//!   reads `.dead` or `.nextid` by `#[serde(rename = ".dead")]`, like [`crate::ser`] writes it
+//! * booleans are accepted as `yes`/`no` and `true`/`false`
+//! * numbers are parsed from their text
+//! * sequences are split at `,`
+//! * a missing or empty attribute is `None` for an `Option`, as RouterOS prints unset values empty.
+//!   A field that should read an empty string must not be an `Option`
+//!
+//! # Examples
+//! ```
+//! use mikrotik_api::{de::from_sentence, values::ItemId};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Interface {
+//!     id: ItemId,
+//!     name: String,
+//!     #[serde(rename = "rx-byte")]
+//!     rx_byte: u64,
+//!     disabled: bool,
+//!     comment: Option<String>,
+//! }
+//!
+//! let interface: Interface = from_sentence(&[
+//!     (b".id", Some(b"*1")),
+//!     (b"name", Some(b"ether1")),
+//!     (b"rx-byte", Some(b"1024")),
+//!     (b"disabled", Some(b"false")),
+//! ])
+//! .unwrap();
+//! assert_eq!(interface.id, ItemId(1));
+//! assert_eq!(interface.rx_byte, 1024);
+//! assert!(interface.comment.is_none());
+//! ```
+use encoding_rs::mem::decode_latin1;
+use serde::{
+    de::{
+        value::{BorrowedStrDeserializer, SeqDeserializer},
+        DeserializeSeed, Error as _, IntoDeserializer, MapAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+use thiserror::Error;
+
+/// A sentence does not match the type it is deserialized into
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Cannot deserialize sentence: {0}")]
+pub struct DeserializeError(Box<str>);
+
+impl serde::de::Error for DeserializeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        DeserializeError(msg.to_string().into_boxed_str())
+    }
+}
+
+/// Deserializes the attributes of a reply sentence, as passed to
+/// [`ParsedMessage::parse_message`](crate::prelude::ParsedMessage::parse_message)
+pub fn from_sentence<'de, T: Deserialize<'de>>(
+    sentence: &'de [(&'de [u8], Option<&'de [u8]>)],
+) -> Result<T, DeserializeError> {
+    T::deserialize(SentenceDeserializer { sentence })
+}
+
+/// Presents the attributes of a sentence as map
+pub struct SentenceDeserializer<'de> {
+    sentence: &'de [(&'de [u8], Option<&'de [u8]>)],
+}
+
+impl<'de> SentenceDeserializer<'de> {
+    pub fn new(sentence: &'de [(&'de [u8], Option<&'de [u8]>)]) -> Self {
+        Self { sentence }
+    }
+}
+
+impl<'de> Deserializer<'de> for SentenceDeserializer<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(AttributeAccess {
+            attributes: self.sentence.iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct AttributeAccess<'de, I> {
+    attributes: I,
+    value: Option<Option<&'de [u8]>>,
+}
+
+impl<'de, I: Iterator<Item = &'de (&'de [u8], Option<&'de [u8]>)>> MapAccess<'de>
+    for AttributeAccess<'de, I>
+{
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.attributes.next() else {
+            return Ok(None);
+        };
+        self.value = Some(*value);
+        let key = if *key == b".id" { b"id" } else { *key };
+        seed.deserialize(ValueDeserializer { value: Some(key) })
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| DeserializeError::custom("value requested before key"))?;
+        seed.deserialize(ValueDeserializer { value })
+    }
+}
+
+/// Deserializes a single attribute value, `None` if the attribute has no value at all
+struct ValueDeserializer<'de> {
+    value: Option<&'de [u8]>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn text(&self) -> Cow<'de, str> {
+        decode_latin1(self.value.unwrap_or_default())
+    }
+
+    fn parse<T: FromStr>(&self, expected: &str) -> Result<T, DeserializeError> {
+        let text = self.text();
+        text.parse()
+            .map_err(|_| DeserializeError::custom(format!("invalid {expected}: {text:?}")))
+    }
+}
+
+macro_rules! deserialize_from_str {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.parse(stringify!($method))?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.text() {
+            Cow::Borrowed(text) => visitor.visit_borrowed_str(text),
+            Cow::Owned(text) => visitor.visit_string(text),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value.unwrap_or_default() {
+            b"yes" | b"true" => visitor.visit_bool(true),
+            b"no" | b"false" => visitor.visit_bool(false),
+            _ => Err(DeserializeError::custom(format!(
+                "invalid bool: {:?}",
+                self.text()
+            ))),
+        }
+    }
+
+    deserialize_from_str! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_bytes(self.value.unwrap_or_default())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        // The inner type is not known here, so an empty `String` is `None` as well
+        match self.value {
+            None | Some(b"") => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let value = self.value.unwrap_or_default();
+        let items = value
+            .split(|c| *c == b',')
+            .filter(|_| !value.is_empty())
+            .map(|item| ValueDeserializer { value: Some(item) });
+        visitor.visit_seq(SeqDeserializer::new(items))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.text() {
+            Cow::Borrowed(text) => visitor.visit_enum(BorrowedStrDeserializer::new(text)),
+            Cow::Owned(text) => visitor.visit_enum(text.into_deserializer()),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        str string map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeserializeError> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::values::ItemId;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum InterfaceType {
+        Ether,
+        Bridge,
+        Vlan,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Interface<'a> {
+        id: ItemId,
+        name: String,
+        #[serde(rename = "type")]
+        interface_type: InterfaceType,
+        #[serde(rename = "rx-byte")]
+        rx_byte: u64,
+        mtu: Option<u16>,
+        disabled: bool,
+        running: bool,
+        comment: Option<&'a str>,
+        #[serde(rename = "default-name")]
+        default_name: Option<String>,
+    }
+
+    #[test]
+    fn test_struct() {
+        let sentence: &[(&[u8], Option<&[u8]>)] = &[
+            (b".id", Some(b"*1A")),
+            (b"name", Some(b"ether1")),
+            (b"type", Some(b"ether")),
+            (b"rx-byte", Some(b"123456789012")),
+            (b"disabled", Some(b"no")),
+            (b"running", Some(b"true")),
+            (b"comment", Some(b"")),
+            (b"unknown", Some(b"ignored")),
+        ];
+        let interface: Interface = from_sentence(sentence).unwrap();
+        assert_eq!(
+            interface,
+            Interface {
+                id: ItemId(0x1a),
+                name: "ether1".to_string(),
+                interface_type: InterfaceType::Ether,
+                rx_byte: 123456789012,
+                mtu: None,
+                disabled: false,
+                running: true,
+                comment: None,
+                default_name: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_values() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Route {
+            distance: Option<u64>,
+            comment: String,
+            disabled: Option<bool>,
+        }
+        let route: Route = from_sentence(&[
+            (b"distance", Some(b"")),
+            (b"comment", Some(b"")),
+            (b"disabled", Some(b"")),
+        ])
+        .unwrap();
+        assert_eq!(
+            route,
+            Route {
+                distance: None,
+                comment: String::new(),
+                disabled: None,
+            }
+        );
+        let result = from_sentence::<Route>(&[(b"distance", Some(b"x")), (b"comment", Some(b""))]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Flags {
+            disabled: bool,
+        }
+        let result = from_sentence::<Flags>(&[(b"disabled", Some(b"maybe"))]);
+        assert!(result.is_err());
+        let result = from_sentence::<Interface>(&[(b"name", Some(b"ether1"))]);
+        assert!(result.is_err());
+        let result = from_sentence::<HashMap<String, u32>>(&[(b"mtu", Some(b"big"))]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_lists_and_maps() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Bridge {
+            ports: Vec<String>,
+            vlans: Vec<u16>,
+            tagged: Vec<String>,
+        }
+        let bridge: Bridge = from_sentence(&[
+            (b"ports", Some(b"ether1,ether2")),
+            (b"vlans", Some(b"10,20,30")),
+            (b"tagged", Some(b"")),
+        ])
+        .unwrap();
+        assert_eq!(
+            bridge,
+            Bridge {
+                ports: vec!["ether1".to_string(), "ether2".to_string()],
+                vlans: vec![10, 20, 30],
+                tagged: vec![],
+            }
+        );
+
+        let map: HashMap<String, Option<String>> =
+            from_sentence(&[(b".id", Some(b"*1")), (b"flag", None)]).unwrap();
+        assert_eq!(map.get("id"), Some(&Some("*1".to_string())));
+        assert_eq!(map.get("flag"), Some(&None));
+    }
+}
@@ -2,229 +2,1437 @@ use crate::{
     error::Error,
     prelude::CommandBuilder,
     protocol::{
-        command::Command,
+        command::{Command, CommandTimeout, ReplayPolicy},
         error::{MissingWord, ProtocolError},
         word::{next_sentence, TrapCategory, TrapResult, Word, WordCategory, WordType},
         WordSequenceItem,
     },
+    tags::{Tag, TagAllocator},
 };
-use log::error;
+use encoding_rs::mem::decode_latin1;
+use log::{debug, error, warn};
 use std::{
     collections::HashMap,
     fmt::Debug,
+    future::Future,
+    io::ErrorKind,
+    pin::Pin,
     sync::{
-        atomic::{AtomicU16, Ordering},
+        atomic::{AtomicU64, Ordering},
         Arc,
     },
+    task::{Context, Poll},
+    time::Duration,
 };
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpStream, ToSocketAddrs},
-    sync::mpsc,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{lookup_host, TcpStream, ToSocketAddrs},
+    sync::{mpsc, oneshot},
+    time::{sleep_until, timeout, Instant},
 };
-use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
 
 pub trait ParsedMessage: Send + 'static {
     type Context: Send + 'static + Debug + Clone;
     fn parse_message(sentence: &[(&[u8], Option<&[u8]>)], context: &Self::Context) -> Self;
     fn process_error(error: &Error, context: &Self::Context) -> Self;
     fn process_trap(result: TrapResult, context: &Self::Context) -> Self;
+    /// Called on `!empty`, sent by RouterOS 7.18+ instead of any `!re` if a command has no results.
+    /// By default nothing is delivered.
+    fn process_empty(_context: &Self::Context) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    /// Called on `!done` as the last message of a command which completed normally, with the attributes
+    /// of the sentence, e.g. `ret` holding the id of an added item. By default nothing is delivered.
+    fn process_done(
+        _attributes: &[(&[u8], Option<&[u8]>)],
+        _context: &Self::Context,
+    ) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
-#[derive(Debug, Clone)]
+/// The `ret` attribute of a `!done` sentence, e.g. the id of an added item
+pub(crate) fn done_ret(attributes: &[(&[u8], Option<&[u8]>)]) -> Option<Box<str>> {
+    attributes
+        .iter()
+        .find_map(|(key, value)| match (*key, value) {
+            (b"ret", Some(value)) => Some(Box::from(decode_latin1(value))),
+            _ => None,
+        })
+}
+
+/// A command sent by [`MikrotikDevice::send_call`] whose result is not received yet
+#[derive(Debug)]
+pub(crate) struct PendingCall {
+    tag: u16,
+    result: oneshot::Receiver<Result<Option<Box<str>>, Error>>,
+}
+
+#[derive(Debug)]
 pub struct MikrotikDevice<D: ParsedMessage> {
     inner: Arc<InnerMikrotikDevice<D>>,
 }
 
+// Derived `Clone` would require `D: Clone`, though only the connection is shared
+impl<D: ParsedMessage> Clone for MikrotikDevice<D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
 impl<D: ParsedMessage> MikrotikDevice<D> {
-    fn create_command<'a>(&self, command: impl Into<WordSequenceItem<'a>>) -> CommandBuilder {
-        let tag = self.inner.next_tag.fetch_add(1, Ordering::Relaxed);
-        CommandBuilder::new(tag, command)
+    fn disconnected(&self, tag: u16) -> Error {
+        Error::Disconnected {
+            device: self.inner.name.clone(),
+            tag,
+        }
+    }
+    async fn send_request(&self, request: CommandRequest<D>) -> Result<(), Error> {
+        let tag = request.2.value();
+        self.inner
+            .command_tx_send
+            .send(request)
+            .await
+            .map_err(|_| self.disconnected(tag))
     }
     pub async fn send_command<F: FnOnce(CommandBuilder) -> CommandBuilder>(
         &self,
         command: impl Into<WordSequenceItem<'_>>,
         command_builder: F,
         context: D::Context,
-    ) -> ReceiverStream<D> {
-        let cmd = command_builder(self.create_command(command)).build();
+    ) -> Result<CommandStream<D>, Error> {
+        let allocated = self.inner.tags.allocate()?;
+        let tag = allocated.value();
+        let cmd = command_builder(CommandBuilder::new(tag, command)).build();
         let (response_sender, response_receiver) = mpsc::channel(16);
-        self.inner
-            .command_tx_send
-            .send((cmd, response_sender, context))
-            .await
-            .expect("Send command failed");
-        ReceiverStream::new(response_receiver)
+        let responder = Responder::Stream {
+            sender: Some(response_sender),
+            context,
+        };
+        self.send_request((cmd, responder, allocated)).await?;
+        Ok(CommandStream {
+            tag,
+            receiver: ReceiverStream::new(response_receiver),
+            cancel_tx_send: self.inner.cancel_tx_send.clone(),
+            finished: false,
+        })
     }
     pub async fn send_simple_command(
         &self,
         command: impl Into<WordSequenceItem<'_>>,
         context: D::Context,
-    ) -> ReceiverStream<D> {
+    ) -> Result<CommandStream<D>, Error> {
         self.send_command(command, |cb| cb, context).await
     }
+    /// Sends a command whose replies are streamed unparsed, regardless of `D`. A trap is delivered
+    /// as [`Error::Trap`].
+    pub(crate) async fn send_sentences_command<F: FnOnce(CommandBuilder) -> CommandBuilder>(
+        &self,
+        command: impl Into<WordSequenceItem<'_>>,
+        command_builder: F,
+    ) -> Result<CommandStream<Result<SentenceReply, Error>>, Error> {
+        let allocated = self.inner.tags.allocate()?;
+        let tag = allocated.value();
+        let cmd = command_builder(CommandBuilder::new(tag, command)).build();
+        let (response_sender, response_receiver) = mpsc::channel(16);
+        self.send_request((cmd, Responder::Sentences(Some(response_sender)), allocated))
+            .await?;
+        Ok(CommandStream {
+            tag,
+            receiver: ReceiverStream::new(response_receiver),
+            cancel_tx_send: self.inner.cancel_tx_send.clone(),
+            finished: false,
+        })
+    }
+    /// Runs a command to its completion and returns its `ret` value, e.g. the id of an item created by `add`
+    /// (see [`ItemId`](crate::values::ItemId)) or the output of `/system/script/run`.
+    /// Replies are discarded, a trap fails the call with [`Error::Trap`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async fn example(device: mikrotik_api::prelude::MikrotikDevice<mikrotik_api::simple::SimpleResult>) -> Result<(), Box<dyn std::error::Error>> {
+    /// use mikrotik_api::values::ItemId;
+    /// let id: Option<ItemId> = device
+    ///     .call(b"/ip/address/add", |cb| {
+    ///         cb.attribute(b"address", b"10.0.0.1/24")
+    ///             .attribute(b"interface", b"ether1")
+    ///     })
+    ///     .await?
+    ///     .map(|ret| ret.parse())
+    ///     .transpose()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn call<F: FnOnce(CommandBuilder) -> CommandBuilder>(
+        &self,
+        command: impl Into<WordSequenceItem<'_>>,
+        command_builder: F,
+    ) -> Result<Option<Box<str>>, Error> {
+        let pending = self.send_call(command, command_builder).await?;
+        self.call_result(pending).await
+    }
+    /// Sends a command like [`MikrotikDevice::call`] without waiting for its result, so many
+    /// commands can be pipelined before their results are collected by [`MikrotikDevice::call_result`].
+    pub(crate) async fn send_call<F: FnOnce(CommandBuilder) -> CommandBuilder>(
+        &self,
+        command: impl Into<WordSequenceItem<'_>>,
+        command_builder: F,
+    ) -> Result<PendingCall, Error> {
+        let allocated = self.inner.tags.allocate()?;
+        let tag = allocated.value();
+        let cmd = command_builder(CommandBuilder::new(tag, command)).build();
+        let (result_sender, result) = oneshot::channel();
+        self.send_request((cmd, Responder::Call(Some(result_sender)), allocated))
+            .await?;
+        Ok(PendingCall { tag, result })
+    }
+    /// Waits for the result of a command sent by [`MikrotikDevice::send_call`]
+    pub(crate) async fn call_result(
+        &self,
+        pending: PendingCall,
+    ) -> Result<Option<Box<str>>, Error> {
+        let tag = pending.tag;
+        pending.result.await.map_err(|_| self.disconnected(tag))?
+    }
+    /// Cancels the running command with the given tag, e.g. taken from [`CommandStream::tag`],
+    /// and waits until the device confirmed it.
+    ///
+    /// Returns `false` if the device did not know a running command with this tag.
+    pub async fn cancel(&self, tag: u16) -> Result<bool, Error> {
+        let allocated = self.inner.tags.allocate()?;
+        let cancel_tag = allocated.value();
+        let cmd = CommandBuilder::cancel(cancel_tag, tag);
+        let (confirm_sender, confirm_receiver) = oneshot::channel();
+        let responder = Responder::Cancel {
+            target: tag,
+            confirm: Some(confirm_sender),
+        };
+        self.send_request((cmd, responder, allocated)).await?;
+        confirm_receiver
+            .await
+            .map_err(|_| self.disconnected(cancel_tag))?
+    }
+    /// Name of the device used in error messages, usually its address
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+    /// Count of sentences received for no running command, see [`ConnectOptions::orphans`]
+    pub fn orphan_count(&self) -> u64 {
+        self.inner.orphan_count.load(Ordering::Relaxed)
+    }
 }
 
 #[derive(Debug)]
 struct InnerMikrotikDevice<D: ParsedMessage> {
-    command_tx_send: mpsc::Sender<(Command, mpsc::Sender<D>, D::Context)>,
-    next_tag: AtomicU16,
+    command_tx_send: mpsc::Sender<CommandRequest<D>>,
+    cancel_tx_send: mpsc::UnboundedSender<u16>,
+    tags: Arc<TagAllocator>,
+    orphan_count: Arc<AtomicU64>,
+    name: Box<str>,
+}
+
+/// Responses of a running command.
+///
+/// Dropping the stream before the command is completed cancels the command on the device.
+#[derive(Debug)]
+pub struct CommandStream<D> {
+    tag: u16,
+    receiver: ReceiverStream<D>,
+    cancel_tx_send: mpsc::UnboundedSender<u16>,
+    finished: bool,
+}
+
+impl<D> CommandStream<D> {
+    /// Tag of the command on the connection
+    pub fn tag(&self) -> u16 {
+        self.tag
+    }
+
+    /// Cancels the command and waits until the device confirmed it, remaining responses are discarded
+    pub async fn cancel(mut self) {
+        if !self.finished {
+            let _ = self.cancel_tx_send.send(self.tag);
+            while self.receiver.next().await.is_some() {}
+            self.finished = true;
+        }
+    }
+}
+
+impl<D> Stream for CommandStream<D> {
+    type Item = D;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.receiver).poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            self.finished = true;
+        }
+        poll
+    }
+}
+
+impl<D> Drop for CommandStream<D> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.cancel_tx_send.send(self.tag);
+        }
+    }
 }
 
 impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Connect to a device by tcp and login, using the default [`ConnectOptions`]
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the device, usually on port 8728
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect<
+        'u,
+        'p,
+        A: ToSocketAddrs,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        addr: A,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        ConnectOptions::default()
+            .connect(addr, username, password)
+            .await
+    }
+
+    /// Login over an already established bidirectional stream (e.g. an ssh channel, a SOCKS tunnel or
+    /// a [`tokio::io::duplex`] pipe) and serve the connection from a background task,
+    /// using the default [`ConnectOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the device used in error messages
+    /// * `stream` - connected transport to the api service of the device
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect_stream<
+        'u,
+        'p,
+        S: AsyncRead + AsyncWrite + Send + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        name: &str,
+        stream: S,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        ConnectOptions::default()
+            .connect_stream(name, stream, username, password)
+            .await
+    }
+
+    /// Login over an already established transport given as independent read and write halves
+    /// and serve the connection from a background task, using the default [`ConnectOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the device used in error messages
+    /// * `tcp_rx` - receiving half of the transport
+    /// * `tcp_tx` - sending half of the transport
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect_split<
+        'u,
+        'p,
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        name: &str,
+        tcp_rx: R,
+        tcp_tx: W,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        ConnectOptions::default()
+            .connect_split(name, tcp_rx, tcp_tx, username, password)
+            .await
+    }
+
+    /// Connect to a device by tcp and login, using the default [`ConnectOptions`]. When the connection
+    /// is lost, it is reestablished in the background and running commands are handled according to
+    /// their [`ReplayPolicy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the device, usually on port 8728
+    /// * `username` - login name
+    /// * `password` - optional password
+    /// * `reconnect` - backoff between the attempts to reconnect
+    pub async fn connect_reconnecting<
+        'u,
+        'p,
+        A: ToSocketAddrs + Clone + Send + Sync + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        addr: A,
+        username: U,
+        password: Option<P>,
+        reconnect: ReconnectOptions,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        ConnectOptions::default()
+            .connect_reconnecting(addr, username, password, reconnect)
+            .await
+    }
+
+    /// Connect to a device by the given connector and login, using the default [`ConnectOptions`].
+    /// When the connection is lost, the connector is called again in the background and running
+    /// commands are handled according to their [`ReplayPolicy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the device used in error messages
+    /// * `connector` - opens a new transport to the device, given as independent read and write halves
+    /// * `username` - login name
+    /// * `password` - optional password
+    /// * `reconnect` - backoff between the attempts to reconnect
+    pub async fn connect_reconnecting_with<
+        'u,
+        'p,
+        C: FnMut() -> F + Send + 'static,
+        F: Future<Output = std::io::Result<(R, W)>> + Send,
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        name: &str,
+        connector: C,
+        username: U,
+        password: Option<P>,
+        reconnect: ReconnectOptions,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        ConnectOptions::default()
+            .connect_reconnecting_with(name, connector, username, password, reconnect)
+            .await
+    }
+}
+
+/// Timeouts applied while connecting to a device and while running commands
+///
+/// # Examples
+/// ```no_run
+/// # async fn example() -> Result<(), mikrotik_api::error::Error> {
+/// use mikrotik_api::{prelude::*, simple::SimpleResult};
+/// use std::time::Duration;
+/// let device: MikrotikDevice<SimpleResult> = ConnectOptions::default()
+///     .connect_timeout(Duration::from_secs(5))
+///     .login_timeout(Duration::from_secs(10))
+///     .command_timeout(Duration::from_secs(30))
+///     .connect(("192.168.88.1", 8728), b"admin", Some(b"password"))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    connect_timeout: Option<Duration>,
+    login_timeout: Option<Duration>,
+    command_timeout: Option<Duration>,
+    orphans: Option<mpsc::Sender<OrphanSentence>>,
+}
+
+impl ConnectOptions {
+    /// Limits the time to establish the transport to the device (default: no limit)
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+    /// Limits the time the device takes to confirm the login (default: no limit)
+    pub fn login_timeout(mut self, login_timeout: Duration) -> Self {
+        self.login_timeout = Some(login_timeout);
+        self
+    }
+    /// Limits the time every command may run until it is completed (default: no limit).
+    /// Can be overridden per command by [`CommandBuilder::timeout`] and [`CommandBuilder::no_timeout`].
+    pub fn command_timeout(mut self, command_timeout: Duration) -> Self {
+        self.command_timeout = Some(command_timeout);
+        self
+    }
+    /// Receives the sentences for no running command, e.g. late replies of a timed out command,
+    /// instead of discarding them. Sentences are dropped while the channel is full.
+    pub fn orphans(mut self, orphans: mpsc::Sender<OrphanSentence>) -> Self {
+        self.orphans = Some(orphans);
+        self
+    }
+
+    /// Connect to a device by tcp and login
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the device, usually on port 8728
+    /// * `username` - login name
+    /// * `password` - optional password
     pub async fn connect<
         'u,
         'p,
+        D: ParsedMessage,
         A: ToSocketAddrs,
         U: Into<WordSequenceItem<'u>>,
         P: Into<WordSequenceItem<'p>>,
     >(
+        &self,
         addr: A,
         username: U,
         password: Option<P>,
     ) -> Result<MikrotikDevice<D>, Error> {
-        let stream = TcpStream::connect(addr).await.map_err(Arc::new)?;
+        let stream = self.with_connect_timeout(TcpStream::connect(addr)).await?;
         stream.set_nodelay(true).map_err(Arc::new)?;
+        let name = stream.peer_addr().map_err(Arc::new)?.to_string();
 
-        let mut running = true;
         // Split for independent read/write
-        let (mut tcp_rx, mut tcp_tx) = stream.into_split();
-        let (command_tx_send, mut command_tx_recv) =
-            mpsc::channel::<(Command, mpsc::Sender<D>, D::Context)>(16);
-        let mut running_commands = HashMap::new();
-        let tag_sequence: AtomicU16 = Default::default();
-
-        let login_tag = tag_sequence.fetch_add(1, Ordering::Relaxed);
-        let login_packet = CommandBuilder::login(login_tag, username, password);
+        let (tcp_rx, tcp_tx) = stream.into_split();
+        self.connect_split(&name, tcp_rx, tcp_tx, username, password)
+            .await
+    }
+
+    /// Login over an already established bidirectional stream and serve the connection from a background task.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the device used in error messages
+    /// * `stream` - connected transport to the api service of the device
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect_stream<
+        'u,
+        'p,
+        D: ParsedMessage,
+        S: AsyncRead + AsyncWrite + Send + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        &self,
+        name: &str,
+        stream: S,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        let (rx, tx) = tokio::io::split(stream);
+        self.connect_split(name, rx, tx, username, password).await
+    }
+
+    /// Login over an already established transport given as independent read and write halves
+    /// and serve the connection from a background task.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the device used in error messages
+    /// * `tcp_rx` - receiving half of the transport
+    /// * `tcp_tx` - sending half of the transport
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect_split<
+        'u,
+        'p,
+        D: ParsedMessage,
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        &self,
+        name: &str,
+        mut tcp_rx: R,
+        mut tcp_tx: W,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        let (command_tx_send, command_tx_recv) = mpsc::channel::<CommandRequest<D>>(16);
+        let (cancel_tx_send, cancel_tx_recv) = mpsc::unbounded_channel();
+        let tags = Arc::new(TagAllocator::default());
+
+        let credentials = Credentials::new(tags.allocate()?, username, password);
+        let packet_buf = self
+            .with_login_timeout(login(&mut tcp_rx, &mut tcp_tx, &credentials))
+            .await?;
+
+        let command_timeout = self.command_timeout;
+        let mut requests = DeviceRequests {
+            commands: command_tx_recv,
+            cancels: cancel_tx_recv,
+            tags: tags.clone(),
+        };
+        let orphans = Orphans::new(self.orphans.clone());
+        let orphan_count = orphans.count.clone();
+        tokio::spawn(async move {
+            let mut running_commands = HashMap::new();
+            if let Some(error) = run_session(
+                tcp_rx,
+                tcp_tx,
+                packet_buf,
+                &mut running_commands,
+                &mut requests,
+                &orphans,
+                command_timeout,
+            )
+            .await
+            {
+                notify_error(&mut running_commands, &error).await;
+            }
+        });
+
+        let device = MikrotikDevice {
+            inner: Arc::new(InnerMikrotikDevice {
+                command_tx_send,
+                cancel_tx_send,
+                tags,
+                orphan_count,
+                name: Box::from(name),
+            }),
+        };
+        Ok(device)
+    }
+
+    /// Connect to a device by tcp and login. When the connection is lost, it is reestablished
+    /// in the background and running commands are handled according to their [`ReplayPolicy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the device, usually on port 8728
+    /// * `username` - login name
+    /// * `password` - optional password
+    /// * `reconnect` - backoff between the attempts to reconnect
+    pub async fn connect_reconnecting<
+        'u,
+        'p,
+        D: ParsedMessage,
+        A: ToSocketAddrs + Clone + Send + Sync + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        &self,
+        addr: A,
+        username: U,
+        password: Option<P>,
+        reconnect: ReconnectOptions,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        let name = lookup_host(addr.clone())
+            .await
+            .map_err(Arc::new)?
+            .next()
+            .map(|addr| addr.to_string())
+            .ok_or_else(|| Arc::new(std::io::Error::from(ErrorKind::AddrNotAvailable)))?;
+        let connector = move || {
+            let addr = addr.clone();
+            async move {
+                let stream = TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                Ok(stream.into_split())
+            }
+        };
+        self.connect_reconnecting_with(&name, connector, username, password, reconnect)
+            .await
+    }
+
+    /// Connect to a device by the given connector and login. When the connection is lost, the connector
+    /// is called again in the background and running commands are handled according to their [`ReplayPolicy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the device used in error messages
+    /// * `connector` - opens a new transport to the device, given as independent read and write halves
+    /// * `username` - login name
+    /// * `password` - optional password
+    /// * `reconnect` - backoff between the attempts to reconnect
+    pub async fn connect_reconnecting_with<
+        'u,
+        'p,
+        D: ParsedMessage,
+        C: FnMut() -> F + Send + 'static,
+        F: Future<Output = std::io::Result<(R, W)>> + Send,
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        &self,
+        name: &str,
+        mut connector: C,
+        username: U,
+        password: Option<P>,
+        reconnect: ReconnectOptions,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        let (command_tx_send, command_tx_recv) = mpsc::channel::<CommandRequest<D>>(16);
+        let (cancel_tx_send, cancel_tx_recv) = mpsc::unbounded_channel();
+        let tags = Arc::new(TagAllocator::default());
+
+        let credentials = Credentials::new(tags.allocate()?, username, password);
+        let (mut tcp_rx, mut tcp_tx) = self.with_connect_timeout(connector()).await?;
+        let packet_buf = self
+            .with_login_timeout(login(&mut tcp_rx, &mut tcp_tx, &credentials))
+            .await?;
+
+        let options = self.clone();
+        let mut requests = DeviceRequests {
+            commands: command_tx_recv,
+            cancels: cancel_tx_recv,
+            tags: tags.clone(),
+        };
+        let orphans = Orphans::new(self.orphans.clone());
+        let orphan_count = orphans.count.clone();
+        tokio::spawn(async move {
+            let mut running_commands = HashMap::new();
+            let mut session = (tcp_rx, tcp_tx, packet_buf);
+            loop {
+                let (tcp_rx, tcp_tx, packet_buf) = session;
+                let Some(error) = run_session(
+                    tcp_rx,
+                    tcp_tx,
+                    packet_buf,
+                    &mut running_commands,
+                    &mut requests,
+                    &orphans,
+                    options.command_timeout,
+                )
+                .await
+                else {
+                    break;
+                };
+                warn!("Connection lost: {error}, reconnecting");
+                fail_not_replayable(&mut running_commands, &error).await;
+                session = match reconnect_session(
+                    &mut connector,
+                    &credentials,
+                    &options,
+                    &reconnect,
+                    &mut running_commands,
+                    &mut requests,
+                )
+                .await
+                {
+                    Reconnect::Connected(session) => session,
+                    Reconnect::Failed(error) => {
+                        error!("Giving up reconnecting: {error}");
+                        notify_error(&mut running_commands, &error).await;
+                        break;
+                    }
+                    Reconnect::Dropped => break,
+                };
+            }
+        });
+
+        let device = MikrotikDevice {
+            inner: Arc::new(InnerMikrotikDevice {
+                command_tx_send,
+                cancel_tx_send,
+                tags,
+                orphan_count,
+                name: Box::from(name),
+            }),
+        };
+        Ok(device)
+    }
+
+    pub(crate) async fn with_connect_timeout<T, F: Future<Output = std::io::Result<T>>>(
+        &self,
+        connect: F,
+    ) -> Result<T, Error> {
+        match self.connect_timeout {
+            Some(connect_timeout) => timeout(connect_timeout, connect)
+                .await
+                .map_err(|_| Error::ConnectTimeout)?,
+            None => connect.await,
+        }
+        .map_err(|e| Error::Io(Arc::new(e)))
+    }
+
+    async fn with_login_timeout<T, F: Future<Output = Result<T, Error>>>(
+        &self,
+        login: F,
+    ) -> Result<T, Error> {
+        match self.login_timeout {
+            Some(login_timeout) => timeout(login_timeout, login)
+                .await
+                .map_err(|_| Error::LoginTimeout)?,
+            None => login.await,
+        }
+    }
+}
+
+/// Backoff between the attempts of a reconnecting device to reestablish a lost connection
+#[derive(Debug, Clone)]
+pub struct ReconnectOptions {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectOptions {
+    /// Delay before the first attempt, doubled after every failed attempt (default: 1s)
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+    /// Upper limit of the delay between two attempts (default: 60s)
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+    /// Give up after the given count of failed attempts (default: retry forever)
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+}
+
+type CommandRequest<D> = (Command, Responder<D>, Tag);
+
+/// Requests from the handles of a device, served by its background task
+struct DeviceRequests<D: ParsedMessage> {
+    commands: mpsc::Receiver<CommandRequest<D>>,
+    /// Tags of commands to cancel, sent when a [`CommandStream`] is dropped
+    cancels: mpsc::UnboundedReceiver<u16>,
+    tags: Arc<TagAllocator>,
+}
+
+/// A sentence of the device no running command waits for: a reply, trap or `!done` with an unknown tag,
+/// e.g. a late reply of a timed out command, or a reply without any tag
+#[derive(Debug, Clone)]
+pub struct OrphanSentence {
+    pub category: WordCategory,
+    pub tag: Option<u16>,
+    pub attributes: HashMap<Box<str>, Option<Box<str>>>,
+}
+
+/// Forwards the orphan sentences of a device to the optional channel and counts them
+struct Orphans {
+    sender: Option<mpsc::Sender<OrphanSentence>>,
+    count: Arc<AtomicU64>,
+}
+
+impl Orphans {
+    fn new(sender: Option<mpsc::Sender<OrphanSentence>>) -> Self {
+        Self {
+            sender,
+            count: Default::default(),
+        }
+    }
+    fn report(&self, category: WordCategory, tag: Option<u16>, sentence: &[Word<'_>]) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let Some(sender) = &self.sender else {
+            debug!("Discarding {category} sentence with unknown tag {tag:?}");
+            return;
+        };
+        let attributes = sentence
+            .iter()
+            .filter_map(|word| match word {
+                Word::Attribute { key, value } => Some((
+                    Box::from(decode_latin1(key)),
+                    value.map(|value| Box::from(decode_latin1(value))),
+                )),
+                _ => None,
+            })
+            .collect();
+        let orphan = OrphanSentence {
+            category,
+            tag,
+            attributes,
+        };
+        if let Err(e) = sender.try_send(orphan) {
+            warn!("Cannot forward orphan sentence: {e}");
+        }
+    }
+}
+
+/// Attributes of a reply, owned to be streamed regardless of the [`ParsedMessage`] of the device
+pub(crate) type OwnedSentence = Vec<(Box<[u8]>, Option<Box<[u8]>>)>;
+
+/// Reply streamed to a command sent by [`MikrotikDevice::send_sentences_command`]
+#[derive(Debug)]
+pub(crate) enum SentenceReply {
+    Sentence {
+        /// Position among the replies to all commands, orders the replies of different commands
+        sequence: u64,
+        attributes: OwnedSentence,
+    },
+    /// The command is sent again after a reconnect, replies in between are lost
+    Resubscribed,
+}
+
+/// Receiver of the responses to a command
+enum Responder<D: ParsedMessage> {
+    /// Parsed responses are streamed to the caller.
+    /// `None` if the command is cancelled, remaining replies are discarded until `!done`
+    Stream {
+        sender: Option<mpsc::Sender<D>>,
+        context: D::Context,
+    },
+    /// A `/cancel`, reports whether its target was running.
+    /// `None` if nobody waits for the confirmation.
+    Cancel {
+        target: u16,
+        confirm: Option<oneshot::Sender<Result<bool, Error>>>,
+    },
+    /// Only the completion matters, reports the `ret` value of `!done`.
+    /// `None` if the result is already reported.
+    Call(Option<oneshot::Sender<Result<Option<Box<str>>, Error>>>),
+    /// Unparsed replies are streamed to the caller, a trap is reported as error.
+    /// `None` if the command is cancelled, remaining replies are discarded until `!done`
+    Sentences(Option<mpsc::Sender<Result<SentenceReply, Error>>>),
+}
+
+impl<D: ParsedMessage> Responder<D> {
+    /// Nobody waits for the responses anymore
+    fn is_closed(&self) -> bool {
+        match self {
+            Responder::Stream { sender, .. } => sender.as_ref().map_or(true, |s| s.is_closed()),
+            Responder::Cancel { confirm, .. } => confirm.as_ref().map_or(true, |c| c.is_closed()),
+            Responder::Call(result) => result.as_ref().map_or(true, |r| r.is_closed()),
+            Responder::Sentences(sender) => sender.as_ref().map_or(true, |s| s.is_closed()),
+        }
+    }
+    /// The command delivers replies until it is completed or cancelled
+    fn is_stream(&self) -> bool {
+        matches!(self, Responder::Stream { .. } | Responder::Sentences(_))
+    }
+    /// Discards the remaining replies if the receiver is dropped
+    fn close_dropped(&mut self) {
+        match self {
+            Responder::Stream { sender, .. } => {
+                if sender.as_ref().is_some_and(|sender| sender.is_closed()) {
+                    *sender = None;
+                }
+            }
+            Responder::Sentences(sender) => {
+                if sender.as_ref().is_some_and(|sender| sender.is_closed()) {
+                    *sender = None;
+                }
+            }
+            Responder::Cancel { .. } | Responder::Call(_) => {}
+        }
+    }
+    /// Reports the error, all further responses are discarded
+    async fn fail(&mut self, error: &Error) {
+        match self {
+            Responder::Stream { sender, context } => {
+                if let Some(sender) = sender.take() {
+                    if let Err(send_error) = sender.send(D::process_error(error, context)).await {
+                        error!("Error processing error:  {:?} / {:?}", error, send_error);
+                    }
+                }
+            }
+            Responder::Cancel { confirm, .. } => {
+                if let Some(confirm) = confirm.take() {
+                    let _ = confirm.send(Err(error.clone()));
+                }
+            }
+            Responder::Call(result) => {
+                if let Some(result) = result.take() {
+                    let _ = result.send(Err(error.clone()));
+                }
+            }
+            Responder::Sentences(sender) => {
+                if let Some(sender) = sender.take() {
+                    let _ = sender.send(Err(error.clone())).await;
+                }
+            }
+        }
+    }
+}
+
+struct RunningCommand<D: ParsedMessage> {
+    command: Command,
+    responder: Responder<D>,
+    /// Reserved until the command is removed
+    tag: Tag,
+    replied: bool,
+    /// `/cancel` is sent, the command ends with an interrupted trap and `!done`
+    cancelled: bool,
+    deadline: Option<Instant>,
+    /// Written to a connection, so it is resubscribed when replayed after a reconnect
+    written: bool,
+    /// Position in the submission order, commands are replayed in this order after a reconnect
+    sequence: u64,
+}
+
+/// Source of [`RunningCommand::sequence`]
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Source of the sequence of [`SentenceReply::Sentence`]
+static NEXT_REPLY: AtomicU64 = AtomicU64::new(0);
+
+impl<D: ParsedMessage> RunningCommand<D> {
+    fn new((command, responder, tag): CommandRequest<D>) -> Self {
+        Self {
+            command,
+            responder,
+            tag,
+            replied: false,
+            cancelled: false,
+            deadline: None,
+            written: false,
+            sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+    fn replayable(&self) -> bool {
+        !self.cancelled
+            && !matches!(self.responder, Responder::Cancel { .. })
+            && !self.responder.is_closed()
+            && match self.command.replay_policy {
+                ReplayPolicy::Fail => false,
+                ReplayPolicy::Retry => !self.replied,
+                ReplayPolicy::Resubscribe => true,
+            }
+    }
+    /// Starts the timeout of the command, called whenever it is written to the device
+    fn sent(&mut self, default_timeout: Option<Duration>) {
+        self.written = true;
+        self.deadline = match self.command.timeout {
+            CommandTimeout::Default => default_timeout,
+            CommandTimeout::Never => None,
+            CommandTimeout::After(timeout) => Some(timeout),
+        }
+        .map(|timeout| Instant::now() + timeout);
+    }
+}
+
+/// Login data, kept to login again after a reconnect
+struct Credentials {
+    tag: Tag,
+    username: Box<[u8]>,
+    password: Option<Box<[u8]>>,
+}
+
+impl Credentials {
+    fn new<'u, 'p, U: Into<WordSequenceItem<'u>>, P: Into<WordSequenceItem<'p>>>(
+        tag: Tag,
+        username: U,
+        password: Option<P>,
+    ) -> Self {
+        Self {
+            tag,
+            username: username.into().to_bytes(),
+            password: password.map(|password| password.into().to_bytes()),
+        }
+    }
+}
+
+/// Sends the login command and waits for its confirmation, returns the remaining received bytes.
+///
+/// If the device answers with a challenge (RouterOS before 6.43), the legacy login is completed.
+async fn login<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    tcp_rx: &mut R,
+    tcp_tx: &mut W,
+    credentials: &Credentials,
+) -> Result<Vec<u8>, Error> {
+    let login_packet = CommandBuilder::login(
+        credentials.tag.value(),
+        credentials.username.as_ref(),
+        credentials.password.as_deref(),
+    );
+    tcp_tx
+        .write_all(login_packet.data.as_ref())
+        .await
+        .map_err(Arc::new)?;
+
+    let mut packet_buf = Vec::new();
+    let mut challenge_answered = false;
+    loop {
+        let (sentence, inc) = match next_sentence(&packet_buf) {
+            Ok(result) => result,
+            Err(ProtocolError::Incomplete) => {
+                let read = tcp_rx.read_buf(&mut packet_buf).await.map_err(Arc::new)?;
+                if read == 0 {
+                    Err(Error::ConnectionClosed)?;
+                }
+                continue;
+            }
+            Err(e) => Err(Error::Protocol(e))?,
+        };
+        let challenge = match sentence.as_slice() {
+            [Word::Category(WordCategory::Done), Word::Tag(_)] => {
+                return Ok(packet_buf.split_off(inc));
+            }
+            [Word::Category(WordCategory::Done), words @ ..] if !challenge_answered => {
+                words.iter().find_map(|word| match word {
+                    Word::Attribute {
+                        key: b"ret",
+                        value: Some(challenge),
+                    } => Some(*challenge),
+                    _ => None,
+                })
+            }
+            [Word::Category(WordCategory::Trap), words @ ..] => {
+                let mut category = None;
+                let mut message = None;
+                for word in words {
+                    match word {
+                        Word::Attribute {
+                            key: b"category",
+                            value: Some(value),
+                        } => category = TrapCategory::try_from(*value).ok(),
+                        Word::Attribute {
+                            key: b"message",
+                            value,
+                        } => message = *value,
+                        _ => {}
+                    }
+                }
+                return Err(Error::LoginFailed {
+                    category,
+                    message: Box::from(decode_latin1(message.unwrap_or_default())),
+                });
+            }
+            _ => None,
+        };
+        let Some(challenge) = challenge else {
+            let category = match sentence.first() {
+                Some(Word::Category(category)) => *category,
+                _ => Err(ProtocolError::IncompleteSentence(MissingWord::Category))?,
+            };
+            let message = sentence.iter().find_map(|word| match word {
+                Word::Message(message)
+                | Word::Attribute {
+                    key: b"message",
+                    value: Some(message),
+                } => Some(Box::from(decode_latin1(message))),
+                _ => None,
+            });
+            Err(Error::UnexpectedLoginReply { category, message })?
+        };
+        let response_packet = CommandBuilder::legacy_login(
+            credentials.tag.value(),
+            credentials.username.as_ref(),
+            credentials.password.as_deref(),
+            challenge,
+        )?;
+        packet_buf.drain(..inc);
+        challenge_answered = true;
         tcp_tx
-            .write_all(login_packet.data.as_ref())
+            .write_all(response_packet.data.as_ref())
             .await
             .map_err(Arc::new)?;
+    }
+}
 
-        let mut packet_buf = Vec::new();
-        loop {
-            let read = tcp_rx.read_buf(&mut packet_buf).await.map_err(Arc::new)?;
-            if read == 0 {
-                running = false;
-                break;
-            } else {
-                match next_sentence(&packet_buf) {
-                    Ok((sentence, inc)) => {
-                        if let &[Word::Category(WordCategory::Done), Word::Tag(_)] =
-                            sentence.as_slice()
-                        {
-                            packet_buf = packet_buf.split_off(inc);
-                            break;
-                        } else {
-                            Err(Error::LoginFailed)?;
-                        }
-                    }
-                    Err(ProtocolError::Incomplete) => continue,
-                    Err(e) => Err(Error::Protocol(e))?,
-                }
+/// Serves one connection to the device. Commands already registered in `running_commands` are sent first.
+///
+/// Returns the reason if the connection is lost or `None` if all handles to the device are dropped.
+async fn run_session<
+    D: ParsedMessage,
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+>(
+    mut tcp_rx: R,
+    mut tcp_tx: W,
+    mut packet_buf: Vec<u8>,
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    requests: &mut DeviceRequests<D>,
+    orphans: &Orphans,
+    command_timeout: Option<Duration>,
+) -> Option<Error> {
+    let mut replayed: Vec<_> = running_commands.values_mut().collect();
+    replayed.sort_by_key(|running_command| running_command.sequence);
+    for running_command in replayed {
+        if let Responder::Sentences(Some(sender)) = &running_command.responder {
+            if running_command.written {
+                let _ = sender.send(Ok(SentenceReply::Resubscribed)).await;
             }
         }
-
-        tokio::spawn(async move {
-            while running {
-                tokio::select! {
-                    biased;
-                      bytes_read = tcp_rx.read_buf(&mut packet_buf) => match bytes_read {
-                        Ok(0) => {
-                            // Device closed connection
-                            notify_error(&mut running_commands, &Error::ConnectionClosed).await;
-                            running = false;
-                        }
-                        Ok(_) => {
-                            let mut offset=0;
-                            loop{
-                                match next_sentence(&packet_buf[offset..]){
-                                    Ok((sentence, inc)) => {
-                                        offset+=inc;
-                                        if let Err(e)=process_sentence(&sentence, &mut running_commands).await{
-                                            error!("Error processing sentence: {}", e);
-                                            running = false;
-                                        }
-                                    }
-                                    Err(ProtocolError::Incomplete) => {
-                                        if offset < packet_buf.len() {
-                                            packet_buf= packet_buf.split_off(offset);
-                                        }else{
-                                            packet_buf.clear();
-                                        }
-                                        break;
+        if let Err(e) = tcp_tx.write_all(&running_command.command.data).await {
+            return Some(Error::Io(Arc::new(e)));
+        }
+        running_command.sent(command_timeout);
+    }
+    loop {
+        let next_deadline = running_commands
+            .values()
+            .filter_map(|running_command| running_command.deadline)
+            .min();
+        tokio::select! {
+            biased;
+            bytes_read = tcp_rx.read_buf(&mut packet_buf) => match bytes_read {
+                // Device closed connection
+                Ok(0) => return Some(Error::ConnectionClosed),
+                Ok(_) => {
+                    let mut offset = 0;
+                    loop {
+                        match next_sentence(&packet_buf[offset..]) {
+                            Ok((sentence, inc)) => {
+                                offset += inc;
+                                match process_sentence(&sentence, running_commands, orphans).await {
+                                    Ok(()) => {}
+                                    Err(Error::Fatal(message)) => {
+                                        // The device closes the session after a fatal error
+                                        error!("Fatal error from device: {message}");
+                                        let _ = tcp_tx.shutdown().await;
+                                        return Some(Error::Fatal(message));
                                     }
                                     Err(e) => {
-                                        notify_error(&mut running_commands, &Error::Protocol(e)).await;
-                                        running=false;
+                                        error!("Error processing sentence: {}", e);
+                                        return Some(e);
                                     }
                                 }
                             }
-                        }
-                        Err(e) => {
-                            // Error reading from the device, shutdown the connection
-                            notify_error(&mut running_commands, &Error::Io(Arc::new(e))).await;
-                            running=false;
-                        }
-                        },
-                                            // Send commands to the device
-                    maybe_actor_message = command_tx_recv.recv() => match maybe_actor_message {
-                        Some(( Command{ tag, data},response_queue, context)) => {
-                            // Error writing the command to the device, shutdown the connection
-                            match tcp_tx.write_all(&data).await {
-                                Ok(_) => {
-                                    // The command is sent, store the channel to send the responses back
-                                    running_commands.insert(tag, (response_queue,context));
-                                }
-                                Err(e) => {
-                                    // Error writing the command to the device, notify every running command and shutdown the connection
-                                    notify_error(&mut running_commands, &Error::Io(Arc::new(e))).await;
-                                    running = false;
+                            Err(ProtocolError::Incomplete) => {
+                                if offset < packet_buf.len() {
+                                    packet_buf = packet_buf.split_off(offset);
+                                } else {
+                                    packet_buf.clear();
                                 }
+                                break;
                             }
+                            Err(e) => return Some(Error::Protocol(e)),
                         }
-                        None => {
-                            // The actor has been dropped, gracefully shutdown
-                            // Cancel all running commands and shutdown the connection
-                            for (tag, _) in running_commands.drain() {
-                                let cancel_command = CommandBuilder::cancel(tag);
-                                let _ = tcp_tx.write_all(cancel_command.data.as_ref()).await;
-                            }
-                            running = false;
+                    }
+                }
+                // Error reading from the device, shutdown the connection
+                Err(e) => return Some(Error::Io(Arc::new(e))),
+            },
+            // Send commands to the device
+            maybe_actor_message = requests.commands.recv() => match maybe_actor_message {
+                Some(request) => {
+                    let mut running_command = RunningCommand::new(request);
+                    if let Responder::Cancel { target, .. } = running_command.responder {
+                        // The target ends with an interrupted trap, which is expected now
+                        if let Some(target) = running_commands.get_mut(&target) {
+                            target.cancelled = true;
+                            target.deadline = None;
                         }
-                    },
+                    }
+                    let result = tcp_tx.write_all(&running_command.command.data).await;
+                    running_command.sent(command_timeout);
+                    // Store the channel to send the responses back, on error it is notified together with the others
+                    running_commands.insert(running_command.tag.value(), running_command);
+                    if let Err(e) = result {
+                        return Some(Error::Io(Arc::new(e)));
+                    }
+                }
+                None => {
+                    // The actor has been dropped, gracefully shutdown
+                    // Cancel all running commands and shutdown the connection
+                    for (tag, running_command) in running_commands.drain() {
+                        if !running_command.responder.is_stream() {
+                            continue;
+                        }
+                        if let Ok(cancel_tag) = requests.tags.allocate() {
+                            let cancel_command = CommandBuilder::cancel(cancel_tag.value(), tag);
+                            let _ = tcp_tx.write_all(cancel_command.data.as_ref()).await;
+                        }
+                    }
+                    // Final attempt to gracefully close TCP
+                    let _ = tcp_tx.shutdown().await;
+                    return None;
+                }
+            },
+            // Cancel commands whose stream is dropped or cancelled
+            Some(tag) = requests.cancels.recv() => {
+                if let Some(running_command) = running_commands.get_mut(&tag) {
+                    running_command.responder.close_dropped();
+                }
+                if let Err(e) = send_cancel(&mut tcp_tx, running_commands, &requests.tags, tag).await {
+                    return Some(Error::Io(Arc::new(e)));
+                }
+            }
+            // Cancel commands running too long
+            _ = sleep_until(next_deadline.unwrap_or_else(Instant::now)), if next_deadline.is_some() => {
+                let now = Instant::now();
+                let expired: Vec<u16> = running_commands
+                    .iter()
+                    .filter(|(_, running_command)| running_command.deadline.is_some_and(|deadline| deadline <= now))
+                    .map(|(tag, _)| *tag)
+                    .collect();
+                for tag in expired {
+                    let Some(running_command) = running_commands.get_mut(&tag) else {
+                        continue;
+                    };
+                    running_command.deadline = None;
+                    running_command.responder.fail(&Error::Timeout { tag }).await;
+                    // Calls keep running on the device too, so every command but a `/cancel` is cancelled
+                    if matches!(running_command.responder, Responder::Cancel { .. }) {
+                        continue;
+                    }
+                    if let Err(e) = send_cancel(&mut tcp_tx, running_commands, &requests.tags, tag).await {
+                        return Some(Error::Io(Arc::new(e)));
+                    }
                 }
             }
+        }
+    }
+}
 
-            // Final attempt to gracefully close TCP
-            let _ = tcp_tx.shutdown().await;
-        });
+/// Sends `/cancel` for a running command under a new tag, nobody waits for its confirmation
+async fn send_cancel<D: ParsedMessage, W: AsyncWrite + Unpin>(
+    tcp_tx: &mut W,
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    tags: &Arc<TagAllocator>,
+    target: u16,
+) -> std::io::Result<()> {
+    let Some(running_command) = running_commands.get_mut(&target) else {
+        return Ok(());
+    };
+    if running_command.cancelled {
+        return Ok(());
+    }
+    let tag = match tags.allocate() {
+        Ok(tag) => tag,
+        Err(e) => {
+            warn!("Cannot cancel command with tag {target}: {e}");
+            return Ok(());
+        }
+    };
+    running_command.cancelled = true;
+    running_command.deadline = None;
+    let command = CommandBuilder::cancel(tag.value(), target);
+    let result = tcp_tx.write_all(&command.data).await;
+    let responder = Responder::Cancel {
+        target,
+        confirm: None,
+    };
+    running_commands.insert(tag.value(), RunningCommand::new((command, responder, tag)));
+    result
+}
 
-        let device = MikrotikDevice {
-            inner: Arc::new(InnerMikrotikDevice {
-                command_tx_send,
-                next_tag: tag_sequence,
-            }),
+enum Reconnect<R, W> {
+    Connected((R, W, Vec<u8>)),
+    Failed(Error),
+    Dropped,
+}
+
+/// Tries to connect and login with exponential backoff. Commands received meanwhile are queued
+/// in `running_commands` to be sent as soon as the connection is established.
+async fn reconnect_session<
+    D: ParsedMessage,
+    C: FnMut() -> F,
+    F: Future<Output = std::io::Result<(R, W)>>,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+>(
+    connector: &mut C,
+    credentials: &Credentials,
+    options: &ConnectOptions,
+    reconnect: &ReconnectOptions,
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    requests: &mut DeviceRequests<D>,
+) -> Reconnect<R, W> {
+    let mut backoff = reconnect.initial_backoff;
+    let mut attempt = 0;
+    loop {
+        let sleep = tokio::time::sleep(backoff);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                maybe_actor_message = requests.commands.recv() => match maybe_actor_message {
+                    // Neither the cancel nor its target are sent to the device yet, the cancel
+                    // completes as if the device confirmed it
+                    Some((_, Responder::Cancel { target, confirm }, _)) => {
+                        let found = cancel_unsent(running_commands, target);
+                        if let Some(confirm) = confirm {
+                            let _ = confirm.send(Ok(found));
+                        }
+                    }
+                    Some(request) => {
+                        let running_command = RunningCommand::new(request);
+                        running_commands.insert(running_command.tag.value(), running_command);
+                    }
+                    None => return Reconnect::Dropped,
+                },
+                // Not sent to the device yet, nothing to cancel there
+                Some(tag) = requests.cancels.recv() => {
+                    running_commands.remove(&tag);
+                }
+            }
+        }
+        let result = match options.with_connect_timeout(connector()).await {
+            Ok((mut tcp_rx, mut tcp_tx)) => options
+                .with_login_timeout(login(&mut tcp_rx, &mut tcp_tx, credentials))
+                .await
+                .map(|packet_buf| (tcp_rx, tcp_tx, packet_buf)),
+            Err(e) => Err(e),
         };
-        Ok(device)
+        match result {
+            Ok(session) => return Reconnect::Connected(session),
+            Err(e) => {
+                attempt += 1;
+                if reconnect.max_attempts.is_some_and(|max| attempt >= max) {
+                    return Reconnect::Failed(e);
+                }
+                warn!("Reconnect attempt {attempt} failed: {e}");
+                backoff = (backoff * 2).min(reconnect.max_backoff);
+            }
+        }
     }
 }
 
-async fn notify_error<D: ParsedMessage>(
-    running_commands: &mut HashMap<u16, (mpsc::Sender<D>, D::Context)>,
+/// Removes a command which is not sent to the device, its caller sees the same result as after
+/// a cancel on the device: streams end without further replies, a call completes without `ret`.
+///
+/// Returns `false` if there is no such command.
+fn cancel_unsent<D: ParsedMessage>(
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    target: u16,
+) -> bool {
+    let Some(running_command) = running_commands.remove(&target) else {
+        return false;
+    };
+    if let Responder::Call(Some(result)) = running_command.responder {
+        let _ = result.send(Ok(None));
+    }
+    true
+}
+
+/// Reports the lost connection to every command that cannot be sent again
+async fn fail_not_replayable<D: ParsedMessage>(
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
     error: &Error,
 ) {
-    for (_, (queue, context)) in running_commands.drain() {
-        if let Err(send_error) = queue.send(D::process_error(error, &context)).await {
-            error!("Error processing error:  {:?} / {:?}", error, send_error);
+    let failed: Vec<u16> = running_commands
+        .iter()
+        .filter(|(_, running_command)| !running_command.replayable())
+        .map(|(tag, _)| *tag)
+        .collect();
+    for tag in failed {
+        if let Some(mut running_command) = running_commands.remove(&tag) {
+            running_command.responder.fail(error).await;
         }
     }
 }
 
+async fn notify_error<D: ParsedMessage>(
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    error: &Error,
+) {
+    for (_, mut running_command) in running_commands.drain() {
+        running_command.responder.fail(error).await;
+    }
+}
+
 async fn process_sentence<D: ParsedMessage>(
     sentence: &[Word<'_>],
-    running_commands: &mut HashMap<u16, (mpsc::Sender<D>, D::Context)>,
-) -> Result<(), ProtocolError> {
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    orphans: &Orphans,
+) -> Result<(), Error> {
     let mut sentence_iter = sentence.iter();
     let word = sentence_iter
         .next()
@@ -238,20 +1446,76 @@ async fn process_sentence<D: ParsedMessage>(
             expected: &[WordType::Category],
         })
     }?;
+    if category != WordCategory::Fatal {
+        let tag = sentence.iter().find_map(|word| match word {
+            Word::Tag(tag) => Some(*tag),
+            _ => None,
+        });
+        match tag {
+            // Cannot be assigned to any command
+            None => {
+                orphans.report(category, None, sentence);
+                return Ok(());
+            }
+            // A late reply of a command we gave up on must not end the session
+            Some(tag) if !running_commands.contains_key(&tag) => {
+                orphans.report(category, Some(tag), sentence);
+                return Ok(());
+            }
+            Some(_) => {}
+        }
+    }
     match category {
         WordCategory::Done => {
-            let word = sentence_iter
-                .next()
-                .ok_or(ProtocolError::IncompleteSentence(MissingWord::Tag))?;
-            let tag = if let Word::Tag(tag) = word {
-                Ok(*tag)
-            } else {
-                Err(ProtocolError::WordSequence {
-                    word: word.word_type(),
-                    expected: &[WordType::Tag],
-                })
-            }?;
-            running_commands.remove(&tag);
+            let mut found_tag = None;
+            let mut attributes = Vec::new();
+            for word in sentence_iter {
+                match word {
+                    Word::Tag(tag) => {
+                        found_tag = Some(*tag);
+                    }
+                    Word::Attribute { key, value } => {
+                        attributes.push((*key, *value));
+                    }
+                    word => Err(ProtocolError::WordSequence {
+                        word: word.word_type(),
+                        expected: &[WordType::Tag, WordType::Attribute],
+                    })?,
+                }
+            }
+            let tag = found_tag.ok_or(ProtocolError::IncompleteSentence(MissingWord::Tag))?;
+            match running_commands.remove(&tag) {
+                Some(RunningCommand {
+                    responder:
+                        Responder::Cancel {
+                            confirm: Some(confirm),
+                            ..
+                        },
+                    ..
+                }) => {
+                    let _ = confirm.send(Ok(true));
+                }
+                Some(RunningCommand {
+                    responder:
+                        Responder::Stream {
+                            sender: Some(sender),
+                            context,
+                        },
+                    cancelled: false,
+                    ..
+                }) => {
+                    if let Some(message) = D::process_done(&attributes, &context) {
+                        let _ = sender.send(message).await;
+                    }
+                }
+                Some(RunningCommand {
+                    responder: Responder::Call(Some(result)),
+                    ..
+                }) => {
+                    let _ = result.send(Ok(done_ret(&attributes)));
+                }
+                _ => {}
+            }
         }
         WordCategory::Reply => {
             let mut found_tag = None;
@@ -274,10 +1538,21 @@ async fn process_sentence<D: ParsedMessage>(
                     })?,
                 }
             }
-            send_message_back(running_commands, &mut found_tag, |context| {
-                D::parse_message(&attributes, context)
-            })
-            .await?;
+            let reply = || {
+                Ok(SentenceReply::Sentence {
+                    sequence: NEXT_REPLY.fetch_add(1, Ordering::Relaxed),
+                    attributes: attributes
+                        .iter()
+                        .map(|(key, value)| (Box::from(*key), value.map(Box::from)))
+                        .collect(),
+                })
+            };
+            if !send_sentence_back(running_commands, found_tag, reply).await {
+                send_message_back(running_commands, &mut found_tag, |context| {
+                    Some(D::parse_message(&attributes, context))
+                })
+                .await?;
+            }
         }
         WordCategory::Trap => {
             let mut found_category = None;
@@ -315,8 +1590,55 @@ async fn process_sentence<D: ParsedMessage>(
                     })?,
                 }
             }
+            let interrupted = found_category == Some(TrapCategory::ExecutionInterrupted)
+                && found_tag
+                    .and_then(|tag| running_commands.get(&tag))
+                    .is_some_and(|running_command| running_command.cancelled);
+            if interrupted {
+                // Confirmation of our own cancel, the command ends with the following `!done`
+                return Ok(());
+            }
+            if let Some(RunningCommand {
+                responder: Responder::Cancel { confirm, .. },
+                ..
+            }) = found_tag.and_then(|tag| running_commands.get_mut(&tag))
+            {
+                // The target is not running
+                if let Some(confirm) = confirm.take() {
+                    let _ = confirm.send(Ok(false));
+                }
+                return Ok(());
+            }
+            if let Some(RunningCommand {
+                responder: Responder::Call(result),
+                ..
+            }) = found_tag.and_then(|tag| running_commands.get_mut(&tag))
+            {
+                if let Some(result) = result.take() {
+                    let _ = result.send(Err(Error::Trap {
+                        tag: found_tag.unwrap_or_default(),
+                        category: found_category,
+                        message: Box::from(decode_latin1(found_message.unwrap_or_default())),
+                    }));
+                }
+                return Ok(());
+            }
+            let trap = || {
+                Err(match found_message {
+                    // Only delivered to the command of `found_tag`
+                    Some(message) => Error::Trap {
+                        tag: found_tag.unwrap_or_default(),
+                        category: found_category,
+                        message: Box::from(decode_latin1(message)),
+                    },
+                    None => Error::Protocol(ProtocolError::MissingMessageInTrap),
+                })
+            };
+            if send_sentence_back(running_commands, found_tag, trap).await {
+                return Ok(());
+            }
             send_message_back(running_commands, &mut found_tag, |context| {
-                match (found_category, found_message) {
+                Some(match (found_category, found_message) {
                     (category, Some(message)) => {
                         D::process_trap(TrapResult { category, message }, context)
                     }
@@ -324,30 +1646,978 @@ async fn process_sentence<D: ParsedMessage>(
                         &Error::Protocol(ProtocolError::MissingMessageInTrap),
                         context,
                     ),
-                }
+                })
             })
             .await?;
         }
         WordCategory::Fatal => {
-            error!("Fatal error from device")
+            let message = sentence_iter
+                .filter_map(|word| match word {
+                    Word::Message(message) => Some(decode_latin1(message)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            Err(Error::Fatal(message.into()))?
+        }
+        WordCategory::Empty => {
+            let mut found_tag = None;
+            for word in sentence_iter {
+                match word {
+                    Word::Tag(tag) => {
+                        found_tag = Some(*tag);
+                    }
+                    word => Err(ProtocolError::WordSequence {
+                        word: word.word_type(),
+                        expected: &[WordType::Tag],
+                    })?,
+                }
+            }
+            send_message_back(running_commands, &mut found_tag, D::process_empty).await?;
         }
-        WordCategory::Empty => {}
     }
     Ok(())
 }
 
-async fn send_message_back<D: ParsedMessage, F: FnOnce(&D::Context) -> D>(
-    running_commands: &mut HashMap<u16, (mpsc::Sender<D>, D::Context)>,
+/// Delivers a reply or trap to a command streaming unparsed sentences.
+/// Returns `false` if the command is of another kind.
+async fn send_sentence_back<D: ParsedMessage, F: FnOnce() -> Result<SentenceReply, Error>>(
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
+    found_tag: Option<u16>,
+    result_builder: F,
+) -> bool {
+    let Some((tag, running_command)) =
+        found_tag.and_then(|tag| Some((tag, running_commands.get_mut(&tag)?)))
+    else {
+        return false;
+    };
+    let Responder::Sentences(sender) = &running_command.responder else {
+        return false;
+    };
+    running_command.replied = true;
+    if let Some(sender) = sender {
+        if let Err(e) = sender.send(result_builder()).await {
+            error!("Cannot send response on tag {tag}: {:?}", e);
+            running_commands.remove(&tag);
+        }
+    }
+    true
+}
+
+async fn send_message_back<D: ParsedMessage, F: FnOnce(&D::Context) -> Option<D>>(
+    running_commands: &mut HashMap<u16, RunningCommand<D>>,
     found_tag: &mut Option<u16>,
     message_builder: F,
 ) -> Result<(), ProtocolError> {
     let tag = found_tag.ok_or(ProtocolError::IncompleteSentence(MissingWord::Tag))?;
-    let (sender, context) = running_commands
-        .get(&tag)
+    let running_command = running_commands
+        .get_mut(&tag)
         .ok_or(ProtocolError::UnknownTag(tag))?;
-    if let Err(e) = sender.send(message_builder(context)).await {
+    running_command.replied = true;
+    let Responder::Stream {
+        sender: Some(sender),
+        context,
+    } = &running_command.responder
+    else {
+        // Cancelled, discard the remaining replies
+        return Ok(());
+    };
+    let Some(message) = message_builder(context) else {
+        return Ok(());
+    };
+    if let Err(e) = sender.send(message).await {
         error!("Cannot send response on tag {tag}: {:?}", e);
         running_commands.remove(&tag);
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        simple::SimpleResult,
+        testing::{tag_of, MockRouter},
+    };
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn test_connect_stream() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            let login = router.accept_login().await;
+            let command = router.read_sentence().await.unwrap();
+            let tag = format!(".tag={}", tag_of(&command));
+            router.reply(&["!re", &tag, "=name=ether1"]).await;
+            router.reply(&["!done", &tag]).await;
+            (login, command)
+        });
+        let device = MikrotikDevice::<SimpleResult>::connect_stream(
+            "test",
+            client,
+            b"admin",
+            Some(b"secret"),
+        )
+        .await
+        .unwrap();
+        let replies: Vec<_> = device
+            .send_simple_command(b"/interface/print", ())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            replies.as_slice(),
+            [SimpleResult::Sentence(attributes), SimpleResult::Done { ret: None }] if attributes.get("name") == Some(&Some(Box::from("ether1")))
+        ));
+
+        let (login, command) = router.await.unwrap();
+        assert_eq!(
+            login,
+            ["/login", ".tag=0", "=name=admin", "=password=secret"]
+        );
+        assert_eq!(command, ["/interface/print", ".tag=1"]);
+    }
+
+    #[tokio::test]
+    async fn test_connection_closed() {
+        let (client, server) = duplex(1024);
+        let (rx, tx) = tokio::io::split(client);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_split("test", rx, tx, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let replies: Vec<_> = device
+            .send_simple_command(b"/interface/listen", ())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            replies.as_slice(),
+            [SimpleResult::Error(Error::ConnectionClosed)]
+        ));
+        let result = device.send_simple_command(b"/interface/print", ()).await;
+        assert!(matches!(
+            result,
+            Err(Error::Disconnected { device, tag: 2 }) if device.as_ref() == "test"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_login() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            let login = router.read_sentence().await.unwrap();
+            router
+                .reply(&["!done", ".tag=0", "=ret=ebddd18303a54111e2dea05a92ab46b4"])
+                .await;
+            let response = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=0"]).await;
+            (login, response)
+        });
+        MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b"secret"))
+            .await
+            .unwrap();
+        let (login, response) = router.await.unwrap();
+        assert_eq!(
+            login,
+            ["/login", ".tag=0", "=name=admin", "=password=secret"]
+        );
+        assert_eq!(
+            response,
+            [
+                "/login",
+                ".tag=0",
+                "=name=admin",
+                "=response=007319531c22b6b85e160d6ac355c1df2e"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_failed() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&["!done", ".tag=0", "=ret=ebddd18303a54111e2dea05a92ab46b4"])
+                .await;
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&["!trap", ".tag=0", "=message=cannot log in"])
+                .await;
+            router.reply(&["!done", ".tag=0"]).await;
+        });
+        let result = MikrotikDevice::<SimpleResult>::connect_stream(
+            "test",
+            client,
+            b"admin",
+            Some(b"wrong"),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::LoginFailed { category: None, message }) if message.as_ref() == "cannot log in"
+        ));
+
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!trap",
+                    ".tag=0",
+                    "=category=1",
+                    "=message=invalid user name or password (6)",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=0"]).await;
+        });
+        let result = MikrotikDevice::<SimpleResult>::connect_stream(
+            "test",
+            client,
+            b"admin",
+            Some(b"wrong"),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::LoginFailed {
+                category: Some(TrapCategory::ArgumentValueFailure),
+                message
+            }) if message.as_ref() == "invalid user name or password (6)"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_login_unexpected_reply() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.read_sentence().await.unwrap();
+            router.reply(&["!fatal", "not logged in"]).await;
+        });
+        let result =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await;
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedLoginReply {
+                category: WordCategory::Fatal,
+                message: Some(message)
+            }) if message.as_ref() == "not logged in"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fatal() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&["!fatal", "session terminated on request"])
+                .await;
+            // the client closes the connection
+            router.read_sentence().await
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let listen = device
+            .send_simple_command(b"/interface/listen", ())
+            .await
+            .unwrap();
+        let print = device
+            .send_simple_command(b"/interface/print", ())
+            .await
+            .unwrap();
+        for stream in [listen, print] {
+            let replies: Vec<_> = stream.collect().await;
+            assert!(matches!(
+                replies.as_slice(),
+                [SimpleResult::Error(Error::Fatal(message))] if message.as_ref() == "session terminated on request"
+            ));
+        }
+        assert!(router.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_drop() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let listen = router.read_sentence().await.unwrap();
+            router.reply(&["!re", ".tag=1", "=name=ether1"]).await;
+            // the cancel and the next command may arrive in any order
+            let mut received = Vec::new();
+            for _ in 0..2 {
+                let command = router.read_sentence().await.unwrap();
+                let tag = format!(".tag={}", tag_of(&command));
+                if command[0] == "/cancel" {
+                    router
+                        .reply(&["!trap", ".tag=1", "=category=2", "=message=interrupted"])
+                        .await;
+                    router.reply(&["!done", ".tag=1"]).await;
+                } else {
+                    router.reply(&["!re", &tag, "=name=ether1"]).await;
+                }
+                router.reply(&["!done", &tag]).await;
+                received.push(command);
+            }
+            received.sort();
+            (listen, received)
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let mut listen = device
+            .send_command(b"/interface/listen", |cb| cb.no_timeout(), ())
+            .await
+            .unwrap();
+        assert!(matches!(
+            listen.next().await,
+            Some(SimpleResult::Sentence(_))
+        ));
+        drop(listen);
+        // the session is still usable after the cancel
+        let print: Vec<_> = device
+            .send_simple_command(b"/interface/print", ())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            print.as_slice(),
+            [SimpleResult::Sentence(_), SimpleResult::Done { ret: None }]
+        ));
+        let (listen, received) = router.await.unwrap();
+        assert_eq!(listen, ["/interface/listen", ".tag=1"]);
+        assert_eq!(received[0][0], "/cancel");
+        assert_eq!(received[0][2], "=tag=1");
+        assert_eq!(received[1][0], "/interface/print");
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.reply(&["!re", ".tag=1", "=name=ether1"]).await;
+            let cancel = router.read_sentence().await.unwrap();
+            assert_eq!(cancel, ["/cancel", ".tag=2", "=tag=1"]);
+            router.reply(&["!re", ".tag=1", "=name=ether2"]).await;
+            router
+                .reply(&["!trap", ".tag=1", "=category=2", "=message=interrupted"])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            // keep the connection open
+            router.read_sentence().await
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let mut listen = device
+            .send_command(b"/interface/listen", |cb| cb.no_timeout(), ())
+            .await
+            .unwrap();
+        assert_eq!(listen.tag(), 1);
+        assert!(matches!(
+            listen.next().await,
+            Some(SimpleResult::Sentence(_))
+        ));
+        timeout(Duration::from_secs(1), listen.cancel())
+            .await
+            .expect("cancel is not confirmed");
+    }
+
+    #[tokio::test]
+    async fn test_device_cancel() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            let running = router.read_sentence().await.unwrap();
+            router
+                .reply(&["!trap", ".tag=1", "=category=2", "=message=interrupted"])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            let unknown = router.read_sentence().await.unwrap();
+            router
+                .reply(&["!trap", ".tag=3", "=message=unknown command"])
+                .await;
+            router.reply(&["!done", ".tag=3"]).await;
+            (running, unknown)
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let listen = device
+            .send_command(b"/interface/listen", |cb| cb.no_timeout(), ())
+            .await
+            .unwrap();
+        assert!(device.cancel(listen.tag()).await.unwrap());
+        // the interrupted trap confirms the cancel and is not delivered
+        let listen: Vec<_> = listen.collect().await;
+        assert!(listen.is_empty());
+        assert!(!device.cancel(9).await.unwrap());
+        let (running, unknown) = router.await.unwrap();
+        assert_eq!(running, ["/cancel", ".tag=2", "=tag=1"]);
+        assert_eq!(unknown, ["/cancel", ".tag=3", "=tag=9"]);
+    }
+
+    #[tokio::test]
+    async fn test_orphans() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.reply(&["!re", ".tag=42", "=name=ether1"]).await;
+            router
+                .reply(&["!trap", ".tag=43", "=message=no such item"])
+                .await;
+            router.reply(&["!done", ".tag=43"]).await;
+            router.reply(&["!re", ".tag=1", "=name=ether2"]).await;
+            router.reply(&["!done", ".tag=1"]).await;
+        });
+        let (orphan_tx, mut orphan_rx) = mpsc::channel(4);
+        let device = ConnectOptions::default()
+            .orphans(orphan_tx)
+            .connect_stream::<SimpleResult, _, _, _>("test", client, b"admin", Some(b""))
+            .await
+            .unwrap();
+        let print: Vec<_> = device
+            .send_simple_command(b"/interface/print", ())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            print.as_slice(),
+            [SimpleResult::Sentence(attributes), SimpleResult::Done { ret: None }] if attributes.get("name") == Some(&Some(Box::from("ether2")))
+        ));
+        assert_eq!(device.orphan_count(), 3);
+        let orphan = orphan_rx.recv().await.unwrap();
+        assert_eq!(orphan.category, WordCategory::Reply);
+        assert_eq!(orphan.tag, Some(42));
+        assert_eq!(
+            orphan.attributes.get("name"),
+            Some(&Some(Box::from("ether1")))
+        );
+        let orphan = orphan_rx.recv().await.unwrap();
+        assert_eq!(orphan.category, WordCategory::Trap);
+        assert_eq!(orphan.tag, Some(43));
+        let orphan = orphan_rx.recv().await.unwrap();
+        assert_eq!(orphan.category, WordCategory::Done);
+        assert_eq!(orphan.tag, Some(43));
+    }
+
+    #[tokio::test]
+    async fn test_empty_and_untagged() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.reply(&["!trap", "=message=untagged"]).await;
+            router.reply(&["!empty", ".tag=1"]).await;
+            router.reply(&["!done", ".tag=1"]).await;
+        });
+        let (orphan_tx, mut orphan_rx) = mpsc::channel(4);
+        let device = ConnectOptions::default()
+            .orphans(orphan_tx)
+            .connect_stream::<SimpleResult, _, _, _>("test", client, b"admin", Some(b""))
+            .await
+            .unwrap();
+        let print: Vec<_> = device
+            .send_simple_command(b"/ip/route/print", ())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            print.as_slice(),
+            [SimpleResult::Empty, SimpleResult::Done { ret: None }]
+        ));
+        let orphan = orphan_rx.recv().await.unwrap();
+        assert_eq!(orphan.category, WordCategory::Trap);
+        assert_eq!(orphan.tag, None);
+        assert_eq!(
+            orphan.attributes.get("message"),
+            Some(&Some(Box::from("untagged")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_done() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.reply(&["!done", "=ret=*1A", ".tag=1"]).await;
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let add: Vec<_> = device
+            .send_command(
+                b"/ip/address/add",
+                |cb| cb.attribute(b"address", b"10.0.0.1/24"),
+                (),
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            add.as_slice(),
+            [SimpleResult::Done { ret: Some(ret) }] if ret.as_ref() == "*1A"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let add = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=1", "=ret=*1A"]).await;
+            router.read_sentence().await.unwrap();
+            router.reply(&["!re", ".tag=2", "=name=ether1"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!trap",
+                    ".tag=3",
+                    "=category=1",
+                    "=message=failure: already have such address",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=3"]).await;
+            add
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let ret = device
+            .call(b"/ip/address/add", |cb| {
+                cb.attribute(b"address", b"10.0.0.1/24")
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret.as_deref(), Some("*1A"));
+        assert_eq!(
+            ret.unwrap().parse::<crate::values::ItemId>(),
+            Ok(crate::values::ItemId(0x1a))
+        );
+        let ret = device.call(b"/interface/print", |cb| cb).await.unwrap();
+        assert_eq!(ret, None);
+        let result = device
+            .call(b"/ip/address/add", |cb| {
+                cb.attribute(b"address", b"10.0.0.1/24")
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::Trap {
+                tag: 3,
+                category: Some(TrapCategory::ArgumentValueFailure),
+                message
+            }) if message.as_ref() == "failure: already have such address"
+        ));
+        assert_eq!(
+            router.await.unwrap(),
+            ["/ip/address/add", ".tag=1", "=address=10.0.0.1/24"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_timeout() {
+        let (client, _server) = duplex(1024);
+        let result = ConnectOptions::default()
+            .login_timeout(Duration::from_millis(20))
+            .connect_stream::<SimpleResult, _, _, _>("test", client, b"admin", Some(b""))
+            .await;
+        assert!(matches!(result, Err(Error::LoginTimeout)));
+    }
+
+    #[tokio::test]
+    async fn test_command_timeout() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let mut received = Vec::new();
+            for _ in 0..5 {
+                received.push(router.read_sentence().await.unwrap());
+            }
+            router.reply(&["!re", ".tag=2", "=name=ether1"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            received
+        });
+        let device = ConnectOptions::default()
+            .command_timeout(Duration::from_millis(20))
+            .connect_stream::<SimpleResult, _, _, _>("test", client, b"admin", Some(b""))
+            .await
+            .unwrap();
+        let print = device
+            .send_simple_command(b"/interface/print", ())
+            .await
+            .unwrap();
+        let listen = device
+            .send_command(b"/interface/listen", |cb| cb.no_timeout(), ())
+            .await
+            .unwrap();
+        let export = device
+            .send_command(b"/export", |cb| cb.timeout(Duration::from_millis(100)), ())
+            .await
+            .unwrap();
+        let print: Vec<_> = print.collect().await;
+        assert!(matches!(
+            print.as_slice(),
+            [SimpleResult::Error(Error::Timeout { tag: 1 })]
+        ));
+        let export: Vec<_> = export.collect().await;
+        assert!(matches!(
+            export.as_slice(),
+            [SimpleResult::Error(Error::Timeout { tag: 3 })]
+        ));
+        // the listen command is still running
+        let listen: Vec<_> = listen.collect().await;
+        assert!(matches!(
+            listen.as_slice(),
+            [SimpleResult::Sentence(_), SimpleResult::Done { ret: None }]
+        ));
+        let received = router.await.unwrap();
+        assert_eq!(received[3], ["/cancel", ".tag=4", "=tag=1"]);
+        assert_eq!(received[4], ["/cancel", ".tag=5", "=tag=3"]);
+    }
+
+    #[tokio::test]
+    async fn test_call_timeout() {
+        let (client, server) = duplex(1024);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let call = router.read_sentence().await.unwrap();
+            let cancel = router.read_sentence().await.unwrap();
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&["!trap", ".tag=1", "=category=2", "=message=interrupted"])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            router.reply(&["!done", ".tag=3"]).await;
+            [call, cancel]
+        });
+        let device = ConnectOptions::default()
+            .command_timeout(Duration::from_millis(20))
+            .connect_stream::<SimpleResult, _, _, _>("test", client, b"admin", Some(b""))
+            .await
+            .unwrap();
+        let result = device.call(b"/system/reboot", |cb| cb).await;
+        assert!(matches!(result, Err(Error::Timeout { tag: 1 })));
+        // The interrupted call and the confirmation of its cancel are expected
+        let next = device.call(b"/system/identity/print", |cb| cb).await;
+        assert!(matches!(next, Ok(None)));
+        assert_eq!(device.orphan_count(), 0);
+        let [call, cancel] = router.await.unwrap();
+        assert_eq!(call, ["/system/reboot", ".tag=1"]);
+        assert_eq!(cancel, ["/cancel", ".tag=2", "=tag=1"]);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_replay_policy() {
+        let (connection_tx, mut connection_rx) = mpsc::channel(2);
+        let (first_client, first_server) = duplex(1024);
+        let (second_client, second_server) = duplex(1024);
+        connection_tx.send(first_client).await.unwrap();
+        connection_tx.send(second_client).await.unwrap();
+        let connector = move || {
+            let stream = connection_rx.try_recv();
+            async move {
+                stream
+                    .map(tokio::io::split)
+                    .map_err(|_| std::io::ErrorKind::ConnectionRefused.into())
+            }
+        };
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(first_server);
+            router.accept_login().await;
+            for _ in 0..4 {
+                router.read_sentence().await.unwrap();
+            }
+            // retried print already delivered a row before the connection is lost
+            router.reply(&["!re", ".tag=4", "=name=ether1"]).await;
+            drop(router);
+
+            let mut router = MockRouter::new(second_server);
+            router.accept_login().await;
+            let mut replayed = Vec::new();
+            for _ in 0..2 {
+                let command = router.read_sentence().await.unwrap();
+                let tag = format!(".tag={}", tag_of(&command));
+                router.reply(&["!re", &tag, "=name=ether2"]).await;
+                router.reply(&["!done", &tag]).await;
+                replayed.push(command);
+            }
+            replayed
+        });
+        let device = MikrotikDevice::<SimpleResult>::connect_reconnecting_with(
+            "test",
+            connector,
+            b"admin",
+            Some(b"secret"),
+            ReconnectOptions::default().initial_backoff(Duration::from_millis(1)),
+        )
+        .await
+        .unwrap();
+        let fail = device
+            .send_simple_command(b"/ip/address/add", ())
+            .await
+            .unwrap();
+        let listen = device
+            .send_command(
+                b"/interface/listen",
+                |cb| cb.replay_policy(ReplayPolicy::Resubscribe),
+                (),
+            )
+            .await
+            .unwrap();
+        let print = device
+            .send_command(
+                b"/ip/route/print",
+                |cb| cb.replay_policy(ReplayPolicy::Retry),
+                (),
+            )
+            .await
+            .unwrap();
+        let started_print = device
+            .send_command(
+                b"/interface/print",
+                |cb| cb.replay_policy(ReplayPolicy::Retry),
+                (),
+            )
+            .await
+            .unwrap();
+
+        let fail: Vec<_> = fail.collect().await;
+        assert!(matches!(
+            fail.as_slice(),
+            [SimpleResult::Error(Error::ConnectionClosed)]
+        ));
+        let started_print: Vec<_> = started_print.collect().await;
+        assert!(matches!(
+            started_print.as_slice(),
+            [
+                SimpleResult::Sentence(_),
+                SimpleResult::Error(Error::ConnectionClosed)
+            ]
+        ));
+        for stream in [listen, print] {
+            let replies: Vec<_> = stream.collect().await;
+            assert!(matches!(
+                replies.as_slice(),
+                [SimpleResult::Sentence(_), SimpleResult::Done { ret: None }]
+            ));
+        }
+        let replayed: Vec<_> = router
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|command| command[0].clone())
+            .collect();
+        assert_eq!(replayed, ["/interface/listen", "/ip/route/print"]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_while_reconnecting() {
+        let (connection_tx, mut connection_rx) = mpsc::channel(2);
+        let (first_client, first_server) = duplex(1024);
+        let (second_client, second_server) = duplex(1024);
+        connection_tx.send(first_client).await.unwrap();
+        connection_tx.send(second_client).await.unwrap();
+        let connector = move || {
+            let stream = connection_rx.try_recv();
+            async move {
+                stream
+                    .map(tokio::io::split)
+                    .map_err(|_| std::io::ErrorKind::ConnectionRefused.into())
+            }
+        };
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(first_server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            drop(router);
+
+            let mut router = MockRouter::new(second_server);
+            router.accept_login().await;
+            let command = router.read_sentence().await.unwrap();
+            router
+                .reply(&["!done", &format!(".tag={}", tag_of(&command))])
+                .await;
+            command
+        });
+        let device = MikrotikDevice::<SimpleResult>::connect_reconnecting_with(
+            "test",
+            connector,
+            b"admin",
+            Some(b"secret"),
+            ReconnectOptions::default().initial_backoff(Duration::from_millis(100)),
+        )
+        .await
+        .unwrap();
+        let lost = device
+            .send_simple_command(b"/ip/address/print", ())
+            .await
+            .unwrap();
+        let lost: Vec<_> = lost.collect().await;
+        assert!(matches!(
+            lost.as_slice(),
+            [SimpleResult::Error(Error::ConnectionClosed)]
+        ));
+        let listen = device
+            .send_command(
+                b"/interface/listen",
+                |cb| cb.replay_policy(ReplayPolicy::Resubscribe),
+                (),
+            )
+            .await
+            .unwrap();
+        assert!(device.cancel(listen.tag()).await.unwrap());
+        assert!(!device.cancel(listen.tag()).await.unwrap());
+        let listen: Vec<_> = listen.collect().await;
+        assert!(listen.is_empty());
+        device
+            .call(b"/system/identity/print", |cb| cb)
+            .await
+            .unwrap();
+        // Neither the cancelled command nor the cancel reach the device
+        let command = router.await.unwrap();
+        assert_eq!(command[0], "/system/identity/print");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_replay_order() {
+        let (connection_tx, mut connection_rx) = mpsc::channel(2);
+        let (first_client, first_server) = duplex(1024);
+        let (second_client, second_server) = duplex(1024);
+        connection_tx.send(first_client).await.unwrap();
+        connection_tx.send(second_client).await.unwrap();
+        let connector = move || {
+            let stream = connection_rx.try_recv();
+            async move {
+                stream
+                    .map(tokio::io::split)
+                    .map_err(|_| std::io::ErrorKind::ConnectionRefused.into())
+            }
+        };
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(first_server);
+            router.accept_login().await;
+            for _ in 0..2 {
+                router.read_sentence().await.unwrap();
+            }
+            drop(router);
+
+            let mut router = MockRouter::new(second_server);
+            router.accept_login().await;
+            let mut replayed = Vec::new();
+            for _ in 0..6 {
+                let command = router.read_sentence().await.unwrap();
+                router
+                    .reply(&["!done", &format!(".tag={}", tag_of(&command))])
+                    .await;
+                replayed.push(command[0].clone());
+            }
+            replayed
+        });
+        let device = MikrotikDevice::<SimpleResult>::connect_reconnecting_with(
+            "test",
+            connector,
+            b"admin",
+            Some(b"secret"),
+            ReconnectOptions::default().initial_backoff(Duration::from_millis(100)),
+        )
+        .await
+        .unwrap();
+        let in_flight = device
+            .send_command(
+                b"/ip/route/print",
+                |cb| cb.replay_policy(ReplayPolicy::Retry),
+                (),
+            )
+            .await
+            .unwrap();
+        let lost = device
+            .send_simple_command(b"/ip/address/print", ())
+            .await
+            .unwrap();
+        // The connection is lost once the command which cannot be replayed fails
+        let lost: Vec<_> = lost.collect().await;
+        assert!(matches!(
+            lost.as_slice(),
+            [SimpleResult::Error(Error::ConnectionClosed)]
+        ));
+        let mut queued = Vec::new();
+        for command in ["/a/add", "/b/set", "/c/remove", "/d/add", "/e/set"] {
+            queued.push(
+                device
+                    .send_command(
+                        command.as_bytes(),
+                        |cb| cb.replay_policy(ReplayPolicy::Retry),
+                        (),
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+        for stream in std::iter::once(in_flight).chain(queued) {
+            let replies: Vec<_> = stream.collect().await;
+            assert!(matches!(
+                replies.as_slice(),
+                [SimpleResult::Done { ret: None }]
+            ));
+        }
+        assert_eq!(
+            router.await.unwrap(),
+            [
+                "/ip/route/print",
+                "/a/add",
+                "/b/set",
+                "/c/remove",
+                "/d/add",
+                "/e/set"
+            ]
+        );
+    }
+}
@@ -1,7 +1,15 @@
 pub use crate::protocol::error::ProtocolError;
+use crate::{
+    protocol::word::{TrapCategory, WordCategory},
+    resource::ResourceError,
+};
 use std::sync::Arc;
 use thiserror::Error;
 
+/// Errors of a device and its commands.
+///
+/// Errors of a command carry its tag, see [`CommandStream::tag`](crate::prelude::CommandStream::tag),
+/// errors of the connection the name of the device where the command does not identify it.
 #[derive(Debug, Error, Clone)]
 pub enum Error {
     #[error("IO Error: {0}")]
@@ -10,6 +18,48 @@ pub enum Error {
     Protocol(#[from] ProtocolError),
     #[error("Connection unexpected closed")]
     ConnectionClosed,
-    #[error("Login failed")]
-    LoginFailed,
+    #[error("Login failed: {message}")]
+    LoginFailed {
+        category: Option<TrapCategory>,
+        message: Box<str>,
+    },
+    #[error("Unexpected reply to login: {category} {}", message.as_deref().unwrap_or_default())]
+    UnexpectedLoginReply {
+        category: WordCategory,
+        message: Option<Box<str>>,
+    },
+    #[error("Device {device} disconnected, command with tag {tag} not sent")]
+    Disconnected { device: Box<str>, tag: u16 },
+    #[error("Timeout connecting to device")]
+    ConnectTimeout,
+    #[error("Timeout waiting for login confirmation")]
+    LoginTimeout,
+    #[error("Command with tag {tag} timed out")]
+    Timeout { tag: u16 },
+    #[error("Fatal error from device: {0}")]
+    Fatal(Box<str>),
+    #[error("All command tags are in use")]
+    TagsExhausted,
+    /// A command of [`ReplayPolicy::Resubscribe`](crate::prelude::ReplayPolicy::Resubscribe) is
+    /// sent again after a reconnect. Replies in between are lost and the items printed before
+    /// may be streamed again, the stream continues after this error.
+    #[error("Command with tag {tag} sent again after a reconnect")]
+    Resubscribed { tag: u16 },
+    #[error("Command with tag {tag} failed: {message}")]
+    Trap {
+        tag: u16,
+        category: Option<TrapCategory>,
+        message: Box<str>,
+    },
+    /// A reply could not be converted into a typed item. It carries no tag, as it is raised by
+    /// the caller after the command delivered its reply, or before any command is sent for
+    /// invalid input. The caller knows the command, e.g. by [`ResourceStream::tag`](crate::resource::ResourceStream::tag).
+    #[error("Invalid reply: {0}")]
+    Resource(#[from] ResourceError),
+    #[cfg(feature = "tls")]
+    #[error("TLS Error: {0}")]
+    Tls(#[from] rustls::Error),
+    #[cfg(feature = "tls")]
+    #[error("Invalid server name: {0}")]
+    InvalidServerName(Box<str>),
 }
@@ -1,11 +1,32 @@
+// Code generated by the derive macros refers to `::mikrotik_api`
+extern crate self as mikrotik_api;
+
+#[cfg(feature = "serde")]
+pub mod de;
 mod device;
 pub mod error;
+pub mod model;
 mod protocol;
+pub mod resource;
 
+#[cfg(feature = "serde")]
+pub mod ser;
 pub mod simple;
+mod tags;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "serde")]
+pub mod typed;
+pub mod values;
 pub mod prelude {
     use crate::{device, protocol};
-    pub use device::{MikrotikDevice, ParsedMessage};
-    pub use protocol::command::CommandBuilder;
-    pub use protocol::word::{TrapCategory, TrapResult};
+    pub use device::{
+        CommandStream, ConnectOptions, MikrotikDevice, OrphanSentence, ParsedMessage,
+        ReconnectOptions,
+    };
+    pub use protocol::command::{CommandBuilder, CommandTimeout, ReplayPolicy};
+    pub use protocol::word::{TrapCategory, TrapResult, WordCategory};
 }
+pub use crate::protocol::error::ProtocolError;
@@ -0,0 +1,642 @@
+//! Models of the firewall address lists and their synchronization
+use crate::{
+    device::PendingCall,
+    error::Error,
+    prelude::{CommandBuilder, MikrotikDevice, ParsedMessage},
+    resource::{ReadableResource, ResourceError, RouterOsResource},
+    values::{IpNet, ItemId, ParseValueError, RosDateTime, RosDuration},
+};
+use std::{
+    collections::{HashSet, VecDeque},
+    net::IpAddr,
+    time::Duration,
+};
+
+/// Number of commands sent by a synchronization before waiting for their results
+const PIPELINE_DEPTH: usize = 256;
+
+/// Entry of `/ip/firewall/address-list`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/firewall/address-list")]
+pub struct AddressListEntry {
+    pub id: Option<ItemId>,
+    pub list: String,
+    /// Address, prefix, range or host name
+    pub address: String,
+    /// Remaining lifetime of a dynamic entry
+    pub timeout: Option<RosDuration>,
+    #[ros(read_only)]
+    pub creation_time: Option<RosDateTime>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    pub disabled: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl AddressListEntry {
+    /// An entry to add to `list`
+    pub fn new(list: impl Into<String>, address: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            list: list.into(),
+            address: address.into(),
+            timeout: None,
+            creation_time: None,
+            dynamic: None,
+            disabled: None,
+            comment: None,
+        }
+    }
+
+    /// Network of the address, `None` for ranges and host names
+    pub fn network(&self) -> Option<IpNet> {
+        self.address
+            .parse::<IpNet>()
+            .map(|net| net.trunc())
+            .or_else(|_| self.address.parse::<IpAddr>().map(IpNet::from))
+            .ok()
+    }
+}
+
+/// Entry of `/ipv6/firewall/address-list`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ipv6AddressListEntry(pub AddressListEntry);
+
+impl ReadableResource for Ipv6AddressListEntry {
+    const PATH: &'static str = "/ipv6/firewall/address-list";
+    const PROPERTIES: &'static [&'static str] = AddressListEntry::PROPERTIES;
+
+    fn from_sentence(sentence: &[(&[u8], Option<&[u8]>)]) -> Result<Self, ResourceError> {
+        AddressListEntry::from_sentence(sentence).map(Self)
+    }
+}
+
+impl RouterOsResource for Ipv6AddressListEntry {
+    fn write_attributes(&self, builder: CommandBuilder) -> CommandBuilder {
+        self.0.write_attributes(builder)
+    }
+}
+
+/// Options of [`MikrotikDevice::sync_address_list_with`]
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    timeout: Option<Duration>,
+    comment: Option<String>,
+    ipv6: bool,
+    dry_run: bool,
+}
+
+impl SyncOptions {
+    /// Adds the entries with this timeout and renews existing entries when less than half of
+    /// it remains (default: permanent entries, entries with a timeout are replaced)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    /// Comment of the added entries
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+    /// Synchronizes `/ipv6/firewall/address-list` instead of `/ip/firewall/address-list`
+    pub fn ipv6(mut self) -> Self {
+        self.ipv6 = true;
+        self
+    }
+    /// Only computes the changes without sending them
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+}
+
+/// Changes of a synchronization, applied unless it was a dry run
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AddressListDiff {
+    /// Desired networks missing in the list
+    pub added: Vec<IpNet>,
+    /// Entries not desired, duplicates, disabled or replaced by a permanent entry
+    pub removed: Vec<AddressListEntry>,
+    /// Entries whose timeout is renewed
+    pub renewed: Vec<AddressListEntry>,
+    /// Number of entries kept as they are
+    pub unchanged: usize,
+}
+
+impl AddressListDiff {
+    /// The list already matches
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renewed.is_empty()
+    }
+
+    fn compute(
+        current: Vec<AddressListEntry>,
+        desired: impl IntoIterator<Item = IpNet>,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let mut missing: HashSet<IpNet> = HashSet::new();
+        let mut added = Vec::new();
+        for net in desired {
+            let net = net.trunc();
+            if missing.insert(net) {
+                added.push(net);
+            }
+        }
+        let mut diff = AddressListDiff::default();
+        for entry in current {
+            let keep = entry.disabled != Some(true)
+                && entry
+                    .network()
+                    .is_some_and(|network| missing.remove(&network));
+            if !keep {
+                diff.removed.push(entry);
+                continue;
+            }
+            match (timeout, entry.timeout) {
+                // Permanent entries never expire
+                (_, None) => diff.unchanged += 1,
+                (None, Some(_)) => {
+                    missing.extend(entry.network());
+                    diff.removed.push(entry);
+                }
+                (Some(timeout), Some(RosDuration(remaining))) if remaining < timeout / 2 => {
+                    diff.renewed.push(entry)
+                }
+                (Some(_), Some(_)) => diff.unchanged += 1,
+            }
+        }
+        added.retain(|net| missing.contains(net));
+        diff.added = added;
+        diff
+    }
+}
+
+/// Failure of a synchronization, holding the changes applied nonetheless.
+///
+/// After an error no further commands are sent, but the results of the commands already sent
+/// are collected, so `applied` matches the list on the device.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{error}")]
+pub struct SyncError {
+    /// Changes confirmed by the device
+    pub applied: AddressListDiff,
+    /// The first error
+    pub error: Error,
+}
+
+impl From<Error> for SyncError {
+    fn from(error: Error) -> Self {
+        Self {
+            applied: AddressListDiff::default(),
+            error,
+        }
+    }
+}
+
+impl From<SyncError> for Error {
+    fn from(error: SyncError) -> Self {
+        error.error
+    }
+}
+
+/// Address of a network as RouterOS prints it, without prefix length for single hosts
+fn entry_address(net: &IpNet) -> String {
+    if net.prefix_len() == net.max_prefix_len() {
+        net.addr().to_string()
+    } else {
+        net.to_string()
+    }
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Reads the entries of an address list of `/ip/firewall/address-list`
+    pub async fn address_list(&self, list: &str) -> Result<Vec<AddressListEntry>, Error> {
+        self.print_filtered(|cb| cb.query_equal(b"list", list.as_bytes()))
+            .await
+    }
+    /// Makes an address list of `/ip/firewall/address-list` hold exactly the `desired` networks
+    /// with the fewest commands, see [`MikrotikDevice::sync_address_list_with`]
+    pub async fn sync_address_list(
+        &self,
+        list: &str,
+        desired: impl IntoIterator<Item = IpNet>,
+    ) -> Result<AddressListDiff, SyncError> {
+        self.sync_address_list_with(list, desired, &SyncOptions::default())
+            .await
+    }
+    /// Makes an address list hold exactly the `desired` networks.
+    ///
+    /// The current entries are printed, the missing networks added and the other entries removed.
+    /// The commands are pipelined, so large lists take few round trips. Returns the changes,
+    /// which are only computed on a dry run.
+    ///
+    /// Entries which vanished in the meantime, e.g. by their timeout, are no error: a removal
+    /// counts as done and an entry to renew is added again. On any other error the changes
+    /// applied so far are returned in [`SyncError::applied`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use mikrotik_api::{model::address_list::SyncOptions, prelude::*};
+    /// # use std::time::Duration;
+    /// # async fn example(device: MikrotikDevice<mikrotik_api::simple::SimpleResult>) -> Result<(), mikrotik_api::error::Error> {
+    /// let blocked = ["192.0.2.0/24".parse().unwrap(), "198.51.100.7/32".parse().unwrap()];
+    /// let options = SyncOptions::default().timeout(Duration::from_secs(86400));
+    /// let diff = device
+    ///     .sync_address_list_with("blocked", blocked, &options.dry_run())
+    ///     .await?;
+    /// println!("would add {} and remove {} entries", diff.added.len(), diff.removed.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn sync_address_list_with(
+        &self,
+        list: &str,
+        desired: impl IntoIterator<Item = IpNet>,
+        options: &SyncOptions,
+    ) -> Result<AddressListDiff, SyncError> {
+        let desired: Vec<IpNet> = desired.into_iter().collect();
+        if let Some(net) = desired
+            .iter()
+            .find(|net| matches!(net, IpNet::V6(_)) != options.ipv6)
+        {
+            return Err(Error::from(ResourceError::Invalid {
+                attribute: "address",
+                error: ParseValueError::new(
+                    if options.ipv6 {
+                        "IPv6 network"
+                    } else {
+                        "IPv4 network"
+                    },
+                    &net.to_string(),
+                ),
+            })
+            .into());
+        }
+        if options.ipv6 {
+            let current: Vec<Ipv6AddressListEntry> = self
+                .print_filtered(|cb| cb.query_equal(b"list", list.as_bytes()))
+                .await?;
+            let current = current.into_iter().map(|entry| entry.0).collect();
+            self.apply_address_list::<Ipv6AddressListEntry>(list, current, desired, options)
+                .await
+        } else {
+            let current = self.address_list(list).await?;
+            self.apply_address_list::<AddressListEntry>(list, current, desired, options)
+                .await
+        }
+    }
+
+    async fn apply_address_list<T: RouterOsResource>(
+        &self,
+        list: &str,
+        current: Vec<AddressListEntry>,
+        desired: Vec<IpNet>,
+        options: &SyncOptions,
+    ) -> Result<AddressListDiff, SyncError> {
+        let diff = AddressListDiff::compute(current, desired, options.timeout);
+        if options.dry_run {
+            return Ok(diff);
+        }
+        let timeout = options.timeout.map(RosDuration);
+        let remove = format!("{}/remove", T::PATH);
+        let set = format!("{}/set", T::PATH);
+        let add = format!("{}/add", T::PATH);
+        let mut changes: VecDeque<Change> = diff
+            .removed
+            .into_iter()
+            .filter(|entry| entry.id.is_some())
+            .map(Change::Remove)
+            .chain(diff.renewed.into_iter().map(Change::Renew))
+            .chain(diff.added.into_iter().map(Change::Add))
+            .collect();
+        let mut applied = AddressListDiff {
+            unchanged: diff.unchanged,
+            ..AddressListDiff::default()
+        };
+        let mut error = None;
+        let mut pending: VecDeque<(PendingCall, Change)> = VecDeque::with_capacity(PIPELINE_DEPTH);
+        loop {
+            // Keep up to PIPELINE_DEPTH commands in flight, stop sending after the first error
+            while pending.len() < PIPELINE_DEPTH && error.is_none() {
+                let Some(change) = changes.pop_front() else {
+                    break;
+                };
+                let call = match &change {
+                    Change::Remove(entry) => {
+                        self.send_call(remove.as_bytes(), |cb| {
+                            cb.value_attribute(b".id", &entry.id)
+                        })
+                        .await
+                    }
+                    Change::Renew(entry) => {
+                        self.send_call(set.as_bytes(), |cb| {
+                            cb.value_attribute(b".id", &entry.id)
+                                .value_attribute(b"timeout", &timeout)
+                        })
+                        .await
+                    }
+                    Change::Add(net) => {
+                        let entry = AddressListEntry {
+                            timeout,
+                            comment: options.comment.clone(),
+                            ..AddressListEntry::new(list, entry_address(net))
+                        };
+                        self.send_call(add.as_bytes(), |cb| entry.write_attributes(cb))
+                            .await
+                    }
+                };
+                match call {
+                    Ok(call) => pending.push_back((call, change)),
+                    Err(e) => error = Some(e),
+                }
+            }
+            let Some((call, change)) = pending.pop_front() else {
+                break;
+            };
+            match (self.call_result(call).await, change) {
+                (Ok(_), change) => applied.record(change),
+                // The entry timed out or was removed by someone else in the meantime
+                (Err(e), Change::Remove(entry)) if is_missing_item(&e) => {
+                    applied.removed.push(entry)
+                }
+                (Err(e), Change::Renew(entry)) if is_missing_item(&e) => {
+                    changes.extend(entry.network().map(Change::Add))
+                }
+                (Err(e), _) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            None => Ok(applied),
+            Some(error) => Err(SyncError { applied, error }),
+        }
+    }
+}
+
+/// A change of an address list to apply
+enum Change {
+    Remove(AddressListEntry),
+    Renew(AddressListEntry),
+    Add(IpNet),
+}
+
+impl AddressListDiff {
+    fn record(&mut self, change: Change) {
+        match change {
+            Change::Remove(entry) => self.removed.push(entry),
+            Change::Renew(entry) => self.renewed.push(entry),
+            Change::Add(net) => self.added.push(net),
+        }
+    }
+}
+
+/// The entry of a `remove` or `set` does not exist (anymore)
+fn is_missing_item(error: &Error) -> bool {
+    matches!(error, Error::Trap { message, .. } if message.as_ref() == "no such item")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simple::SimpleResult, testing::MockRouter};
+    use tokio::io::duplex;
+
+    fn entry(id: u32, address: &str, timeout: Option<u64>) -> AddressListEntry {
+        AddressListEntry {
+            id: Some(ItemId(id)),
+            timeout: timeout.map(|secs| RosDuration(Duration::from_secs(secs))),
+            ..AddressListEntry::new("blocked", address)
+        }
+    }
+
+    fn nets(nets: &[&str]) -> Vec<IpNet> {
+        nets.iter().map(|net| net.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let current = vec![
+            entry(1, "10.0.0.1", None),
+            entry(2, "10.1.0.0/16", None),
+            entry(3, "10.0.0.1", None),
+            entry(4, "example.com", None),
+            entry(5, "10.2.0.0/16", Some(60)),
+        ];
+        let diff = AddressListDiff::compute(
+            current.clone(),
+            nets(&["10.0.0.1/32", "10.2.3.4/16", "10.3.0.0/16", "10.3.0.0/16"]),
+            None,
+        );
+        assert_eq!(diff.added, nets(&["10.2.0.0/16", "10.3.0.0/16"]));
+        assert_eq!(
+            diff.removed,
+            [
+                current[1].clone(),
+                current[2].clone(),
+                current[3].clone(),
+                current[4].clone()
+            ]
+        );
+        assert_eq!(diff.unchanged, 1);
+
+        let diff = AddressListDiff::compute(
+            current.clone(),
+            nets(&["10.0.0.1/32", "10.2.0.0/16"]),
+            Some(Duration::from_secs(3600)),
+        );
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.renewed, [current[4].clone()]);
+        assert_eq!(diff.unchanged, 1);
+
+        let diff = AddressListDiff::compute(
+            current.clone(),
+            nets(&["10.0.0.1/32", "10.2.0.0/16"]),
+            Some(Duration::from_secs(100)),
+        );
+        assert!(diff.renewed.is_empty());
+        assert_eq!(diff.unchanged, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let mut sentences = vec![router.read_sentence().await.unwrap()];
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*1",
+                    "=list=blocked",
+                    "=address=192.0.2.0/24",
+                    "=creation-time=2024-01-02 10:00:00",
+                    "=dynamic=false",
+                    "=disabled=false",
+                ])
+                .await;
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*2",
+                    "=list=blocked",
+                    "=address=198.51.100.7",
+                    "=timeout=2h",
+                    "=dynamic=true",
+                    "=disabled=false",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            // All changes are sent before the first result
+            for _ in 0..3 {
+                sentences.push(router.read_sentence().await.unwrap());
+            }
+            router.reply(&["!done", ".tag=2"]).await;
+            router.reply(&["!done", ".tag=3"]).await;
+            router.reply(&["!done", ".tag=4", "=ret=*3"]).await;
+            sentences
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let desired = nets(&["198.51.100.7/32", "203.0.113.0/24"]);
+
+        let options = SyncOptions::default()
+            .timeout(Duration::from_secs(86400))
+            .comment("sync");
+        let diff = device
+            .sync_address_list_with("blocked", desired.clone(), &options)
+            .await
+            .unwrap();
+        assert_eq!(diff.added, nets(&["203.0.113.0/24"]));
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.renewed.len(), 1);
+
+        let sentences = router.await.unwrap();
+        assert_eq!(
+            sentences,
+            [
+                vec![
+                    "/ip/firewall/address-list/print",
+                    ".tag=1",
+                    "=.proplist=.id,list,address,timeout,creation-time,dynamic,disabled,comment",
+                    "?list=blocked"
+                ],
+                vec!["/ip/firewall/address-list/remove", ".tag=2", "=.id=*1"],
+                vec![
+                    "/ip/firewall/address-list/set",
+                    ".tag=3",
+                    "=.id=*2",
+                    "=timeout=1d"
+                ],
+                vec![
+                    "/ip/firewall/address-list/add",
+                    ".tag=4",
+                    "=list=blocked",
+                    "=address=203.0.113.0/24",
+                    "=timeout=1d",
+                    "=comment=sync"
+                ],
+            ]
+        );
+        assert!(matches!(
+            device
+                .sync_address_list("blocked", nets(&["2001:db8::/32"]))
+                .await,
+            Err(SyncError {
+                error: Error::Resource(ResourceError::Invalid { .. }),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sync_errors() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            for (id, address, timeout) in [
+                ("*1", "192.0.2.0/24", None),
+                ("*2", "198.51.100.7", Some("1m")),
+                ("*5", "192.0.2.9", None),
+            ] {
+                let id = format!("=.id={id}");
+                let address = format!("=address={address}");
+                let timeout = timeout.map(|timeout| format!("=timeout={timeout}"));
+                let mut words = vec!["!re", ".tag=1", &id, "=list=blocked", &address];
+                words.extend(timeout.as_deref());
+                router.reply(&words).await;
+            }
+            router.reply(&["!done", ".tag=1"]).await;
+            let mut sentences = Vec::new();
+            for _ in 0..4 {
+                sentences.push(router.read_sentence().await.unwrap());
+            }
+            for (tag, message) in [
+                (2, Some("no such item")),
+                (3, None),
+                (4, Some("no such item")),
+                (5, Some("failure: already have such entry")),
+            ] {
+                let tag = format!(".tag={tag}");
+                if let Some(message) = message {
+                    let message = format!("=message={message}");
+                    router.reply(&["!trap", &tag, &message]).await;
+                }
+                router.reply(&["!done", &tag]).await;
+            }
+            // The entry to renew timed out, so it is added again
+            sentences.push(router.read_sentence().await.unwrap());
+            router.reply(&["!done", ".tag=6", "=ret=*6"]).await;
+            sentences
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let options = SyncOptions::default().timeout(Duration::from_secs(86400));
+        let error = device
+            .sync_address_list_with(
+                "blocked",
+                nets(&["198.51.100.7/32", "203.0.113.0/24"]),
+                &options,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.error,
+            Error::Trap { tag: 5, ref message, .. } if message.as_ref() == "failure: already have such entry"
+        ));
+        let removed: Vec<_> = error.applied.removed.iter().map(|entry| entry.id).collect();
+        assert_eq!(removed, [Some(ItemId(1)), Some(ItemId(5))]);
+        assert!(error.applied.renewed.is_empty());
+        assert_eq!(error.applied.added, nets(&["198.51.100.7/32"]));
+
+        let sentences = router.await.unwrap();
+        assert_eq!(
+            sentences[2],
+            [
+                "/ip/firewall/address-list/set",
+                ".tag=4",
+                "=.id=*2",
+                "=timeout=1d"
+            ]
+        );
+        assert_eq!(
+            sentences[4],
+            [
+                "/ip/firewall/address-list/add",
+                ".tag=6",
+                "=list=blocked",
+                "=address=198.51.100.7",
+                "=timeout=1d"
+            ]
+        );
+    }
+}
@@ -0,0 +1,652 @@
+//! Models of the `/ip/dhcp-server` menu for leases
+use crate::{
+    device::{OwnedSentence, SentenceReply},
+    error::Error,
+    prelude::{CommandStream, MikrotikDevice, ParsedMessage, ReplayPolicy},
+    resource::{parse_attribute, ReadableResource, RouterOsResource},
+    values::{named_enum, ItemId, MacAddress, RosDuration},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Debug, Formatter},
+    future::{poll_fn, Future},
+    net::IpAddr,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio_stream::{Stream, StreamExt};
+
+named_enum! {
+    /// State of a lease in the address assignment
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum LeaseStatus {
+        /// Not used by a client, e.g. a static lease of an offline client
+        Waiting = "waiting",
+        /// Address is checked to be free before it is offered
+        Testing = "testing",
+        Authorizing = "authorizing",
+        /// Address is used by another device
+        Busy = "busy",
+        Offered = "offered",
+        Bound = "bound";
+        /// Any status without own variant
+        Other(Box<str>),
+    }
+}
+
+/// Lease of a DHCP server, `/ip/dhcp-server/lease`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/dhcp-server/lease")]
+pub struct Lease {
+    pub id: Option<ItemId>,
+    /// Address assigned to the client
+    pub address: Option<IpAddr>,
+    pub mac_address: Option<MacAddress>,
+    pub client_id: Option<String>,
+    /// Server the lease belongs to, `all` for static leases of any server
+    pub server: Option<String>,
+    #[ros(read_only)]
+    pub host_name: Option<String>,
+    #[ros(read_only)]
+    pub status: Option<LeaseStatus>,
+    /// Time until a bound lease ends if the client does not renew it
+    #[ros(read_only)]
+    pub expires_after: Option<RosDuration>,
+    #[ros(read_only)]
+    pub active_address: Option<IpAddr>,
+    #[ros(read_only)]
+    pub active_mac_address: Option<MacAddress>,
+    /// Lease was created by a client instead of configured as static binding
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub blocked: Option<bool>,
+    pub disabled: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl Lease {
+    /// A static binding of `address` to the client with the given mac address
+    pub fn new(address: IpAddr, mac_address: MacAddress) -> Self {
+        Self {
+            id: None,
+            address: Some(address),
+            mac_address: Some(mac_address),
+            client_id: None,
+            server: None,
+            host_name: None,
+            status: None,
+            expires_after: None,
+            active_address: None,
+            active_mac_address: None,
+            dynamic: None,
+            blocked: None,
+            disabled: None,
+            comment: None,
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic == Some(true)
+    }
+}
+
+/// Change of the leases reported by [`LeaseWatch`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LeaseEvent {
+    Added(Lease),
+    Changed(Lease),
+    Removed(ItemId),
+}
+
+/// Events of the leases of a device, started by [`MikrotikDevice::watch_leases`].
+///
+/// After a reconnect the leases are printed again and compared with the known state, so the
+/// changes missed in between are reported as well. Dropping the stream cancels the `listen`
+/// command on the device.
+pub struct LeaseWatch<D: ParsedMessage> {
+    device: MikrotikDevice<D>,
+    /// Last state of every lease
+    known: HashMap<ItemId, Lease>,
+    /// Events found by a snapshot, delivered before any further reply of `listen`
+    events: VecDeque<LeaseEvent>,
+    snapshot: Option<Snapshot>,
+    stream: CommandStream<Result<SentenceReply, Error>>,
+}
+
+/// Leases printed by a [`Snapshot`], each with the position of its reply
+type PrintedLeases = Result<Vec<(u64, Lease)>, Error>;
+
+/// A print of the current leases, replies of `listen` are buffered until it completed.
+/// Every item and reply holds its position among all replies of the connection.
+struct Snapshot {
+    print: Pin<Box<dyn Future<Output = PrintedLeases> + Send>>,
+    buffered: Vec<(u64, OwnedSentence)>,
+}
+
+impl Snapshot {
+    fn start<D: ParsedMessage>(device: &MikrotikDevice<D>) -> Self {
+        let device = device.clone();
+        let print = async move {
+            let proplist = Lease::PROPERTIES.join(",");
+            let mut stream = device
+                .send_sentences_command(b"/ip/dhcp-server/lease/print", |cb| {
+                    cb.attribute(b".proplist", proplist.as_bytes())
+                })
+                .await?;
+            let mut leases = Vec::new();
+            while let Some(reply) = stream.next().await {
+                if let SentenceReply::Sentence {
+                    sequence,
+                    attributes: sentence,
+                } = reply?
+                {
+                    leases.push((sequence, Lease::from_sentence(&attributes(&sentence))?));
+                }
+            }
+            Ok(leases)
+        };
+        Self {
+            print: Box::pin(print),
+            buffered: Vec::new(),
+        }
+    }
+}
+
+impl<D: ParsedMessage> Debug for LeaseWatch<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LeaseWatch")
+            .field("known", &self.known)
+            .field("events", &self.events)
+            .field("snapshot", &self.snapshot.is_some())
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl<D: ParsedMessage> LeaseWatch<D> {
+    /// Tag of the `listen` command on the connection
+    pub fn tag(&self) -> u16 {
+        self.stream.tag()
+    }
+
+    /// Cancels the `listen` command and waits until the device confirmed it
+    pub async fn cancel(self) {
+        self.stream.cancel().await
+    }
+
+    /// Drives a running snapshot to its completion. The replies of `listen` are read meanwhile,
+    /// otherwise they would fill its channel and block the replies of the print.
+    fn poll_snapshot(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let Some(snapshot) = &mut self.snapshot else {
+            return Poll::Ready(Ok(()));
+        };
+        while let Poll::Ready(reply) = Pin::new(&mut self.stream).poll_next(cx) {
+            match reply {
+                Some(Ok(SentenceReply::Sentence {
+                    sequence,
+                    attributes,
+                })) => snapshot.buffered.push((sequence, attributes)),
+                // The replies before are superseded by a new print
+                Some(Ok(SentenceReply::Resubscribed)) => *snapshot = Snapshot::start(&self.device),
+                Some(Err(error)) => {
+                    self.snapshot = None;
+                    return Poll::Ready(Err(error));
+                }
+                None => {
+                    self.snapshot = None;
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+        let leases = ready!(snapshot.print.as_mut().poll(cx));
+        let buffered = self
+            .snapshot
+            .take()
+            .map(|snapshot| snapshot.buffered)
+            .unwrap_or_default();
+        self.apply_snapshot(leases?, buffered)?;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Reports the differences of the printed leases to the known state, followed by the
+    /// buffered replies which are newer than the printed state of their lease
+    fn apply_snapshot(
+        &mut self,
+        leases: Vec<(u64, Lease)>,
+        buffered: Vec<(u64, OwnedSentence)>,
+    ) -> Result<(), Error> {
+        let mut previous = std::mem::take(&mut self.known);
+        let mut printed = HashMap::new();
+        for (sequence, lease) in leases {
+            let Some(id) = lease.id else {
+                continue;
+            };
+            printed.insert(id, sequence);
+            match previous.remove(&id) {
+                None => self.events.push_back(LeaseEvent::Added(lease.clone())),
+                Some(old) if old != lease => {
+                    self.events.push_back(LeaseEvent::Changed(lease.clone()))
+                }
+                Some(_) => {}
+            }
+            self.known.insert(id, lease);
+        }
+        let mut removed: Vec<_> = previous.into_keys().collect();
+        removed.sort();
+        self.events
+            .extend(removed.into_iter().map(LeaseEvent::Removed));
+        for (sequence, sentence) in buffered {
+            let attributes = attributes(&sentence);
+            let id: Option<ItemId> = parse_attribute(".id", value(&attributes, b".id"))?;
+            // The print already shows the changes before it read the lease
+            if id
+                .and_then(|id| printed.get(&id))
+                .is_some_and(|printed| sequence < *printed)
+            {
+                continue;
+            }
+            let event = self.event(&attributes)?;
+            self.events.push_back(event);
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, attributes: &[(&[u8], Option<&[u8]>)]) -> Result<LeaseEvent, Error> {
+        // A removed item is reported by its id only
+        if parse_attribute::<Option<bool>>(".dead", value(attributes, b".dead"))? == Some(true) {
+            let id: ItemId = parse_attribute(".id", value(attributes, b".id"))?;
+            self.known.remove(&id);
+            return Ok(LeaseEvent::Removed(id));
+        }
+        let lease = Lease::from_sentence(attributes)?;
+        let Some(id) = lease.id else {
+            return Ok(LeaseEvent::Added(lease));
+        };
+        Ok(match self.known.insert(id, lease.clone()) {
+            Some(_) => LeaseEvent::Changed(lease),
+            None => LeaseEvent::Added(lease),
+        })
+    }
+}
+
+fn attributes(sentence: &OwnedSentence) -> Vec<(&[u8], Option<&[u8]>)> {
+    sentence
+        .iter()
+        .map(|(key, value)| (key.as_ref(), value.as_deref()))
+        .collect()
+}
+
+fn value<'a>(attributes: &[(&[u8], Option<&'a [u8]>)], name: &[u8]) -> Option<Option<&'a [u8]>> {
+    attributes
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| *value)
+}
+
+impl<D: ParsedMessage> Stream for LeaseWatch<D> {
+    type Item = Result<LeaseEvent, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.events.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.snapshot.is_some() {
+                if let Err(error) = ready!(this.poll_snapshot(cx)) {
+                    return Poll::Ready(Some(Err(error)));
+                }
+                continue;
+            }
+            let sentence = match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+                Some(Ok(SentenceReply::Sentence { attributes, .. })) => attributes,
+                // Changes while disconnected are lost, they are found by comparing a new print
+                Some(Ok(SentenceReply::Resubscribed)) => {
+                    this.snapshot = Some(Snapshot::start(&this.device));
+                    continue;
+                }
+                Some(Err(error)) => return Poll::Ready(Some(Err(error))),
+                None => return Poll::Ready(None),
+            };
+            return Poll::Ready(Some(this.event(&attributes(&sentence))));
+        }
+    }
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Reads the leases of all DHCP servers
+    pub async fn dhcp_leases(&self) -> Result<Vec<Lease>, Error> {
+        self.print().await
+    }
+    /// Converts a dynamic lease into a static binding of its address to the client
+    pub async fn make_static_lease(&self, id: ItemId) -> Result<(), Error> {
+        self.call(b"/ip/dhcp-server/lease/make-static", |cb| {
+            cb.value_attribute(b"numbers", &id)
+        })
+        .await?;
+        Ok(())
+    }
+    /// Sets the comment of a lease, an empty comment removes it
+    pub async fn set_lease_comment(&self, id: ItemId, comment: &str) -> Result<(), Error> {
+        self.call(b"/ip/dhcp-server/lease/set", |cb| {
+            cb.value_attribute(b".id", &id)
+                .value_attribute(b"comment", comment)
+        })
+        .await?;
+        Ok(())
+    }
+    /// Removes a lease, a static binding is deleted and a dynamic client has to request a new lease
+    pub async fn remove_lease(&self, id: ItemId) -> Result<(), Error> {
+        self.remove::<Lease>(id).await
+    }
+    /// Streams the changes of the leases until the stream is dropped, starting with an
+    /// [`LeaseEvent::Added`] for every current lease. The `listen` command is restarted after
+    /// a reconnect.
+    pub async fn watch_leases(&self) -> Result<LeaseWatch<D>, Error> {
+        // Listens before printing, so no change between both is lost
+        let stream = self
+            .send_sentences_command(b"/ip/dhcp-server/lease/listen", |cb| {
+                cb.no_timeout().replay_policy(ReplayPolicy::Resubscribe)
+            })
+            .await?;
+        let mut watch = LeaseWatch {
+            device: self.clone(),
+            known: HashMap::new(),
+            events: VecDeque::new(),
+            snapshot: Some(Snapshot::start(self)),
+            stream,
+        };
+        poll_fn(|cx| watch.poll_snapshot(cx)).await?;
+        Ok(watch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::ReconnectOptions,
+        simple::SimpleResult,
+        testing::{tag_of, MockRouter},
+    };
+    use std::time::Duration;
+    use tokio::{io::duplex, sync::mpsc};
+    use tokio_stream::StreamExt;
+
+    #[test]
+    fn test_lease_status() {
+        assert_eq!("bound".parse(), Ok(LeaseStatus::Bound));
+        assert_eq!(
+            "conflict".parse(),
+            Ok(LeaseStatus::Other(Box::from("conflict")))
+        );
+        for (name, status) in LeaseStatus::NAMES {
+            assert_eq!(status.to_string(), *name);
+            assert_eq!(name.parse(), Ok(status.clone()));
+        }
+    }
+
+    #[test]
+    fn test_parse_recorded() {
+        let lease = Lease::from_sentence(&[
+            (b".id", Some(b"*1A")),
+            (b"address", Some(b"192.168.88.254")),
+            (b"mac-address", Some(b"DC:A6:32:01:02:03")),
+            (b"client-id", Some(b"1:dc:a6:32:1:2:3")),
+            (b"address-lists", Some(b"")),
+            (b"server", Some(b"defconf")),
+            (b"dhcp-option", Some(b"")),
+            (b"status", Some(b"bound")),
+            (b"expires-after", Some(b"8m51s")),
+            (b"last-seen", Some(b"1m9s")),
+            (b"active-address", Some(b"192.168.88.254")),
+            (b"active-mac-address", Some(b"DC:A6:32:01:02:03")),
+            (b"active-client-id", Some(b"1:dc:a6:32:1:2:3")),
+            (b"active-server", Some(b"defconf")),
+            (b"host-name", Some(b"raspberrypi")),
+            (b"radius", Some(b"false")),
+            (b"dynamic", Some(b"true")),
+            (b"blocked", Some(b"false")),
+            (b"disabled", Some(b"false")),
+        ])
+        .unwrap();
+        assert_eq!(lease.id, Some(ItemId(0x1a)));
+        assert_eq!(lease.status, Some(LeaseStatus::Bound));
+        assert_eq!(lease.host_name.as_deref(), Some("raspberrypi"));
+        assert_eq!(lease.expires_after.unwrap().0.as_secs(), 531);
+        assert!(lease.is_dynamic());
+
+        let lease = Lease::from_sentence(&[
+            (b".id", Some(b"*2")),
+            (b"address", Some(b"192.168.88.10")),
+            (b"mac-address", Some(b"48:8F:5A:01:02:03")),
+            (b"server", Some(b"all")),
+            (b"status", Some(b"waiting")),
+            (b"last-seen", Some(b"never")),
+            (b"dynamic", Some(b"false")),
+            (b"disabled", Some(b"false")),
+            (b"comment", Some(b"printer")),
+        ])
+        .unwrap();
+        assert_eq!(lease.status, Some(LeaseStatus::Waiting));
+        assert_eq!(lease.expires_after, None);
+        assert!(!lease.is_dynamic());
+    }
+
+    #[tokio::test]
+    async fn test_watch_leases() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let listen = router.read_sentence().await.unwrap();
+            let print = router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=2",
+                    "=.id=*1",
+                    "=address=192.168.88.10",
+                    "=status=waiting",
+                    "=dynamic=false",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=2"]).await;
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*2",
+                    "=address=192.168.88.254",
+                    "=host-name=raspberrypi",
+                    "=status=offered",
+                    "=dynamic=true",
+                ])
+                .await;
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*2",
+                    "=address=192.168.88.254",
+                    "=host-name=raspberrypi",
+                    "=status=bound",
+                    "=dynamic=true",
+                ])
+                .await;
+            router
+                .reply(&["!re", ".tag=1", "=.id=*1", "=.dead=yes"])
+                .await;
+            let make_static = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=3"]).await;
+            let comment = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=4"]).await;
+            let remove = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=5"]).await;
+            [listen, print, make_static, comment, remove]
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let mut watch = device.watch_leases().await.unwrap();
+        let mut events = Vec::new();
+        for _ in 0..4 {
+            events.push(watch.next().await.unwrap().unwrap());
+        }
+        assert!(matches!(&events[0], LeaseEvent::Added(lease) if lease.id == Some(ItemId(1))));
+        assert!(
+            matches!(&events[1], LeaseEvent::Added(lease) if lease.status == Some(LeaseStatus::Offered))
+        );
+        assert!(
+            matches!(&events[2], LeaseEvent::Changed(lease) if lease.status == Some(LeaseStatus::Bound))
+        );
+        assert_eq!(events[3], LeaseEvent::Removed(ItemId(1)));
+        device.make_static_lease(ItemId(2)).await.unwrap();
+        device
+            .set_lease_comment(ItemId(2), "raspberry")
+            .await
+            .unwrap();
+        device.remove_lease(ItemId(2)).await.unwrap();
+
+        let [listen, print, make_static, comment, remove] = router.await.unwrap();
+        assert_eq!(listen, ["/ip/dhcp-server/lease/listen", ".tag=1"]);
+        assert_eq!(print[0], "/ip/dhcp-server/lease/print");
+        assert_eq!(
+            make_static,
+            ["/ip/dhcp-server/lease/make-static", ".tag=3", "=numbers=*2"]
+        );
+        assert_eq!(
+            comment,
+            [
+                "/ip/dhcp-server/lease/set",
+                ".tag=4",
+                "=.id=*2",
+                "=comment=raspberry"
+            ]
+        );
+        assert_eq!(
+            remove,
+            ["/ip/dhcp-server/lease/remove", ".tag=5", "=.id=*2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch_leases_busy() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.read_sentence().await.unwrap();
+            // Older than the printed state of the lease
+            router
+                .reply(&["!re", ".tag=1", "=.id=*1", "=status=offered"])
+                .await;
+            router
+                .reply(&["!re", ".tag=2", "=.id=*1", "=status=bound"])
+                .await;
+            // More changes than the channel of listen holds before the print completes
+            for id in 0x10..0x30 {
+                let id = format!("=.id=*{id:X}");
+                router
+                    .reply(&["!re", ".tag=1", &id, "=status=offered"])
+                    .await;
+            }
+            router
+                .reply(&["!re", ".tag=1", "=.id=*1", "=.dead=yes"])
+                .await;
+            router.reply(&["!done", ".tag=2"]).await;
+            router
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let mut watch = device.watch_leases().await.unwrap();
+        let _router = router.await.unwrap();
+        let mut events = Vec::new();
+        for _ in 0..34 {
+            events.push(watch.next().await.unwrap().unwrap());
+        }
+        assert!(
+            matches!(&events[0], LeaseEvent::Added(lease) if lease.status == Some(LeaseStatus::Bound))
+        );
+        for (event, id) in events[1..33].iter().zip(0x10..) {
+            assert!(matches!(event, LeaseEvent::Added(lease) if lease.id == Some(ItemId(id))));
+        }
+        assert_eq!(events[33], LeaseEvent::Removed(ItemId(1)));
+    }
+
+    #[tokio::test]
+    async fn test_watch_leases_resubscribe() {
+        let (connection_tx, mut connection_rx) = mpsc::channel(2);
+        let (first_client, first_server) = duplex(4096);
+        let (second_client, second_server) = duplex(4096);
+        connection_tx.send(first_client).await.unwrap();
+        connection_tx.send(second_client).await.unwrap();
+        let connector = move || {
+            let stream = connection_rx.try_recv();
+            async move {
+                stream
+                    .map(tokio::io::split)
+                    .map_err(|_| std::io::ErrorKind::ConnectionRefused.into())
+            }
+        };
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(first_server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&["!re", ".tag=2", "=.id=*1", "=status=waiting"])
+                .await;
+            router
+                .reply(&["!re", ".tag=2", "=.id=*2", "=status=offered"])
+                .await;
+            router.reply(&["!done", ".tag=2"]).await;
+            drop(router);
+
+            // Changes while disconnected are found by a new print
+            let mut router = MockRouter::new(second_server);
+            router.accept_login().await;
+            let listen = router.read_sentence().await.unwrap();
+            let print = router.read_sentence().await.unwrap();
+            let tag = format!(".tag={}", tag_of(&print));
+            router
+                .reply(&["!re", &tag, "=.id=*2", "=status=bound"])
+                .await;
+            router
+                .reply(&["!re", &tag, "=.id=*3", "=status=offered"])
+                .await;
+            router.reply(&["!done", &tag]).await;
+            [listen, print]
+        });
+        let device = MikrotikDevice::<SimpleResult>::connect_reconnecting_with(
+            "test",
+            connector,
+            b"admin",
+            Some(b""),
+            ReconnectOptions::default().initial_backoff(Duration::from_millis(1)),
+        )
+        .await
+        .unwrap();
+        let mut watch = device.watch_leases().await.unwrap();
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            events.push(watch.next().await.unwrap().unwrap());
+        }
+        assert!(matches!(&events[0], LeaseEvent::Added(lease) if lease.id == Some(ItemId(1))));
+        assert!(matches!(&events[1], LeaseEvent::Added(lease) if lease.id == Some(ItemId(2))));
+        assert!(
+            matches!(&events[2], LeaseEvent::Changed(lease) if lease.status == Some(LeaseStatus::Bound))
+        );
+        assert!(matches!(&events[3], LeaseEvent::Added(lease) if lease.id == Some(ItemId(3))));
+        assert_eq!(events[4], LeaseEvent::Removed(ItemId(1)));
+
+        let [listen, print] = router.await.unwrap();
+        assert_eq!(listen, ["/ip/dhcp-server/lease/listen", ".tag=1"]);
+        assert_eq!(print[0], "/ip/dhcp-server/lease/print");
+    }
+}
@@ -0,0 +1,491 @@
+//! Models of the `/ip/firewall` menus for filter, nat and mangle rules
+use crate::{
+    error::Error,
+    prelude::{MikrotikDevice, ParsedMessage},
+    resource::{parse_attribute, ReadableResource, ResourceError, RouterOsResource},
+    values::{named_enum, ItemId, RosDuration, RosList},
+};
+use std::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+};
+
+named_enum! {
+    /// Chain of a rule, one of the built-in chains of its table or a custom chain used by `jump`.
+    /// Defaults to `forward`, the chain of routed packets
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+    pub enum Chain {
+        Input = "input",
+        #[default]
+        Forward = "forward",
+        Output = "output",
+        Prerouting = "prerouting",
+        Postrouting = "postrouting",
+        Srcnat = "srcnat",
+        Dstnat = "dstnat";
+        Custom(Box<str>),
+    }
+}
+
+named_enum! {
+    /// What a rule does with a matching packet
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum FirewallAction {
+        Accept = "accept",
+        Drop = "drop",
+        Reject = "reject",
+        Tarpit = "tarpit",
+        /// Continues in the chain given by `jump-target`
+        Jump = "jump",
+        Return = "return",
+        Log = "log",
+        Passthrough = "passthrough",
+        FasttrackConnection = "fasttrack-connection",
+        AddSrcToAddressList = "add-src-to-address-list",
+        AddDstToAddressList = "add-dst-to-address-list",
+        Masquerade = "masquerade",
+        SrcNat = "src-nat",
+        DstNat = "dst-nat",
+        Netmap = "netmap",
+        Redirect = "redirect",
+        EndpointIndependentNat = "endpoint-independent-nat",
+        MarkConnection = "mark-connection",
+        MarkPacket = "mark-packet",
+        MarkRouting = "mark-routing",
+        ChangeMss = "change-mss",
+        ChangeTtl = "change-ttl",
+        ChangeDscp = "change-dscp";
+        /// Any action without own variant
+        Other(Box<str>),
+    }
+}
+
+/// Rule of one of the firewall tables, its position within the table is significant
+pub trait FirewallRule: RouterOsResource {}
+
+/// Rule of `/ip/firewall/filter`.
+///
+/// Matchers take the RouterOS syntax, e.g. `!10.0.0.0/8` or `10.0.0.1-10.0.0.9` as address and
+/// `80,443` or `1000-2000` as port. `bytes` and `packets` count the matched traffic.
+#[derive(Debug, Clone, Default, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/firewall/filter")]
+pub struct FilterRule {
+    pub id: Option<ItemId>,
+    pub chain: Chain,
+    pub action: Option<FirewallAction>,
+    pub jump_target: Option<String>,
+    pub reject_with: Option<String>,
+    pub protocol: Option<String>,
+    pub src_address: Option<String>,
+    pub dst_address: Option<String>,
+    pub src_port: Option<String>,
+    pub dst_port: Option<String>,
+    pub in_interface: Option<String>,
+    pub out_interface: Option<String>,
+    pub in_interface_list: Option<String>,
+    pub out_interface_list: Option<String>,
+    pub src_address_list: Option<String>,
+    pub dst_address_list: Option<String>,
+    pub connection_state: Option<RosList<String>>,
+    pub connection_mark: Option<String>,
+    /// Address list of the `add-src-to-address-list` and `add-dst-to-address-list` actions
+    pub address_list: Option<String>,
+    pub address_list_timeout: Option<RosDuration>,
+    pub log: Option<bool>,
+    pub log_prefix: Option<String>,
+    #[ros(read_only)]
+    pub bytes: Option<u64>,
+    #[ros(read_only)]
+    pub packets: Option<u64>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub invalid: Option<bool>,
+    pub disabled: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl FirewallRule for FilterRule {}
+
+impl FilterRule {
+    /// A rule to add, matching every packet of the chain
+    pub fn new(chain: Chain, action: FirewallAction) -> Self {
+        Self {
+            chain,
+            action: Some(action),
+            ..Default::default()
+        }
+    }
+}
+
+/// Rule of `/ip/firewall/nat`, see [`FilterRule`] for the matchers
+#[derive(Debug, Clone, Default, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/firewall/nat")]
+pub struct NatRule {
+    pub id: Option<ItemId>,
+    pub chain: Chain,
+    pub action: Option<FirewallAction>,
+    pub jump_target: Option<String>,
+    /// Translated addresses of `src-nat`, `dst-nat` and `netmap`
+    pub to_addresses: Option<String>,
+    pub to_ports: Option<String>,
+    pub protocol: Option<String>,
+    pub src_address: Option<String>,
+    pub dst_address: Option<String>,
+    pub src_port: Option<String>,
+    pub dst_port: Option<String>,
+    pub in_interface: Option<String>,
+    pub out_interface: Option<String>,
+    pub in_interface_list: Option<String>,
+    pub out_interface_list: Option<String>,
+    pub src_address_list: Option<String>,
+    pub dst_address_list: Option<String>,
+    pub connection_mark: Option<String>,
+    pub address_list: Option<String>,
+    pub address_list_timeout: Option<RosDuration>,
+    pub log: Option<bool>,
+    pub log_prefix: Option<String>,
+    #[ros(read_only)]
+    pub bytes: Option<u64>,
+    #[ros(read_only)]
+    pub packets: Option<u64>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub invalid: Option<bool>,
+    pub disabled: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl FirewallRule for NatRule {}
+
+impl NatRule {
+    /// A rule to add, matching every packet of the chain
+    pub fn new(chain: Chain, action: FirewallAction) -> Self {
+        Self {
+            chain,
+            action: Some(action),
+            ..Default::default()
+        }
+    }
+}
+
+/// Rule of `/ip/firewall/mangle`, see [`FilterRule`] for the matchers
+#[derive(Debug, Clone, Default, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/firewall/mangle")]
+pub struct MangleRule {
+    pub id: Option<ItemId>,
+    pub chain: Chain,
+    pub action: Option<FirewallAction>,
+    pub jump_target: Option<String>,
+    pub new_connection_mark: Option<String>,
+    pub new_packet_mark: Option<String>,
+    pub new_routing_mark: Option<String>,
+    /// Packet continues to the following rules after a mark action
+    pub passthrough: Option<bool>,
+    pub protocol: Option<String>,
+    pub src_address: Option<String>,
+    pub dst_address: Option<String>,
+    pub src_port: Option<String>,
+    pub dst_port: Option<String>,
+    pub in_interface: Option<String>,
+    pub out_interface: Option<String>,
+    pub in_interface_list: Option<String>,
+    pub out_interface_list: Option<String>,
+    pub src_address_list: Option<String>,
+    pub dst_address_list: Option<String>,
+    pub connection_state: Option<RosList<String>>,
+    pub connection_mark: Option<String>,
+    pub packet_mark: Option<String>,
+    pub routing_mark: Option<String>,
+    pub address_list: Option<String>,
+    pub address_list_timeout: Option<RosDuration>,
+    pub log: Option<bool>,
+    pub log_prefix: Option<String>,
+    #[ros(read_only)]
+    pub bytes: Option<u64>,
+    #[ros(read_only)]
+    pub packets: Option<u64>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub invalid: Option<bool>,
+    pub disabled: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl FirewallRule for MangleRule {}
+
+impl MangleRule {
+    /// A rule to add, matching every packet of the chain
+    pub fn new(chain: Chain, action: FirewallAction) -> Self {
+        Self {
+            chain,
+            action: Some(action),
+            ..Default::default()
+        }
+    }
+}
+
+/// Traffic counters of a rule of the table of `T`, a read-only view printed without the other attributes
+pub struct RuleCounters<T> {
+    pub id: ItemId,
+    pub bytes: u64,
+    pub packets: u64,
+    rule: PhantomData<fn() -> T>,
+}
+
+impl<T> RuleCounters<T> {
+    pub fn new(id: ItemId, bytes: u64, packets: u64) -> Self {
+        Self {
+            id,
+            bytes,
+            packets,
+            rule: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for RuleCounters<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuleCounters")
+            .field("id", &self.id)
+            .field("bytes", &self.bytes)
+            .field("packets", &self.packets)
+            .finish()
+    }
+}
+
+impl<T> Clone for RuleCounters<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.id, self.bytes, self.packets)
+    }
+}
+
+impl<T> PartialEq for RuleCounters<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.id, self.bytes, self.packets) == (other.id, other.bytes, other.packets)
+    }
+}
+
+impl<T: FirewallRule> ReadableResource for RuleCounters<T> {
+    const PATH: &'static str = T::PATH;
+    const PROPERTIES: &'static [&'static str] = &[".id", "bytes", "packets"];
+
+    fn from_sentence(sentence: &[(&[u8], Option<&[u8]>)]) -> Result<Self, ResourceError> {
+        let value = |name: &str| {
+            sentence
+                .iter()
+                .find(|(key, _)| *key == name.as_bytes())
+                .map(|(_, value)| *value)
+        };
+        Ok(Self::new(
+            parse_attribute(".id", value(".id"))?,
+            parse_attribute("bytes", value("bytes"))?,
+            parse_attribute("packets", value("packets"))?,
+        ))
+    }
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Reads the rules of the firewall table of `T` in their order
+    pub async fn firewall_rules<T: FirewallRule>(&self) -> Result<Vec<T>, Error> {
+        self.print().await
+    }
+    /// Adds a rule in front of the rule `before` instead of at the end of the table
+    pub async fn add_rule_before<T: FirewallRule>(
+        &self,
+        rule: &T,
+        before: ItemId,
+    ) -> Result<ItemId, Error> {
+        self.add_with(rule, |cb| cb.value_attribute(b"place-before", &before))
+            .await
+    }
+    /// Moves the rule `id` in front of the rule `before`, to the end of the table if `None`
+    pub async fn move_rule<T: FirewallRule>(
+        &self,
+        id: ItemId,
+        before: Option<ItemId>,
+    ) -> Result<(), Error> {
+        self.call(format!("{}/move", T::PATH).as_bytes(), |cb| {
+            cb.value_attribute(b"numbers", &id)
+                .value_attribute(b"destination", &before)
+        })
+        .await?;
+        Ok(())
+    }
+    /// Reads the traffic counters of all rules of the table of `T`
+    pub async fn rule_counters<T: FirewallRule>(&self) -> Result<Vec<RuleCounters<T>>, Error> {
+        self.print().await
+    }
+    /// Sets the traffic counters of the rule `id` to zero
+    pub async fn reset_rule_counters<T: FirewallRule>(&self, id: ItemId) -> Result<(), Error> {
+        self.call(format!("{}/reset-counters", T::PATH).as_bytes(), |cb| {
+            cb.value_attribute(b"numbers", &id)
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simple::SimpleResult, testing::MockRouter};
+    use std::time::Duration;
+    use tokio::io::duplex;
+
+    #[test]
+    fn test_names() {
+        assert_eq!("srcnat".parse(), Ok(Chain::Srcnat));
+        assert_eq!("vpn-in".parse(), Ok(Chain::Custom(Box::from("vpn-in"))));
+        assert_eq!(Chain::Custom(Box::from("vpn-in")).to_string(), "vpn-in");
+        assert_eq!(
+            "fasttrack-connection".parse(),
+            Ok(FirewallAction::FasttrackConnection)
+        );
+        assert_eq!(
+            "sniff-tzsp".parse(),
+            Ok(FirewallAction::Other(Box::from("sniff-tzsp")))
+        );
+        for (name, chain) in Chain::NAMES {
+            assert_eq!(chain.to_string(), *name);
+            assert_eq!(name.parse(), Ok(chain.clone()));
+        }
+        for (name, action) in FirewallAction::NAMES {
+            assert_eq!(action.to_string(), *name);
+            assert_eq!(name.parse(), Ok(action.clone()));
+        }
+    }
+
+    #[test]
+    fn test_parse_recorded() {
+        let rule = FilterRule::from_sentence(&[
+            (b".id", Some(b"*2")),
+            (b"chain", Some(b"input")),
+            (b"action", Some(b"accept")),
+            (b"connection-state", Some(b"established,related,untracked")),
+            (b"bytes", Some(b"1953372")),
+            (b"packets", Some(b"17840")),
+            (b"invalid", Some(b"false")),
+            (b"dynamic", Some(b"false")),
+            (b"disabled", Some(b"false")),
+            (
+                b"comment",
+                Some(b"defconf: accept established,related,untracked"),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(rule.chain, Chain::Input);
+        assert_eq!(rule.action, Some(FirewallAction::Accept));
+        assert_eq!(
+            rule.connection_state.map(|states| states.0),
+            Some(vec![
+                "established".to_string(),
+                "related".to_string(),
+                "untracked".to_string()
+            ])
+        );
+        assert_eq!((rule.bytes, rule.packets), (Some(1953372), Some(17840)));
+
+        let rule = NatRule::from_sentence(&[
+            (b".id", Some(b"*1")),
+            (b"chain", Some(b"srcnat")),
+            (b"action", Some(b"masquerade")),
+            (b"out-interface-list", Some(b"WAN")),
+            (b"ipsec-policy", Some(b"out,none")),
+            (b"bytes", Some(b"0")),
+            (b"packets", Some(b"0")),
+            (b"invalid", Some(b"false")),
+            (b"dynamic", Some(b"false")),
+            (b"disabled", Some(b"false")),
+            (b"comment", Some(b"defconf: masquerade")),
+        ])
+        .unwrap();
+        assert_eq!(rule.action, Some(FirewallAction::Masquerade));
+        assert_eq!(rule.out_interface_list.as_deref(), Some("WAN"));
+
+        let rule = MangleRule::from_sentence(&[
+            (b".id", Some(b"*3")),
+            (b"chain", Some(b"prerouting")),
+            (b"action", Some(b"mark-routing")),
+            (b"new-routing-mark", Some(b"vpn")),
+            (b"passthrough", Some(b"false")),
+            (b"src-address-list", Some(b"!local")),
+            (b"address-list", Some(b"marked")),
+            (b"address-list-timeout", Some(b"1d12h")),
+            (b"bytes", Some(b"42")),
+            (b"packets", Some(b"1")),
+            (b"disabled", Some(b"true")),
+        ])
+        .unwrap();
+        assert_eq!(rule.new_routing_mark.as_deref(), Some("vpn"));
+        assert_eq!(rule.src_address_list.as_deref(), Some("!local"));
+        assert_eq!(rule.passthrough, Some(false));
+        assert_eq!(
+            rule.address_list_timeout,
+            Some(RosDuration(Duration::from_secs(36 * 3600)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rule_helpers() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let mut sentences = Vec::new();
+            let add = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=1", "=ret=*A"]).await;
+            sentences.push(add);
+            for tag in 2..=4 {
+                sentences.push(router.read_sentence().await.unwrap());
+                router.reply(&["!done", &format!(".tag={tag}")]).await;
+            }
+            sentences.push(router.read_sentence().await.unwrap());
+            router
+                .reply(&["!re", ".tag=5", "=.id=*A", "=bytes=120", "=packets=2"])
+                .await;
+            router.reply(&["!done", ".tag=5"]).await;
+            sentences
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let mut rule = FilterRule::new(Chain::Forward, FirewallAction::Drop);
+        rule.src_address_list = Some("blocked".to_string());
+        rule.comment = Some("drop blocked".to_string());
+        let id = device.add_rule_before(&rule, ItemId(2)).await.unwrap();
+        assert_eq!(id, ItemId(10));
+        device.move_rule::<FilterRule>(id, None).await.unwrap();
+        device.disable::<FilterRule>(id).await.unwrap();
+        device.enable::<FilterRule>(id).await.unwrap();
+        let counters = device.rule_counters::<FilterRule>().await.unwrap();
+        assert_eq!(counters, [RuleCounters::new(ItemId(10), 120, 2)]);
+
+        let sentences = router.await.unwrap();
+        assert_eq!(
+            sentences,
+            [
+                vec![
+                    "/ip/firewall/filter/add",
+                    ".tag=1",
+                    "=chain=forward",
+                    "=action=drop",
+                    "=src-address-list=blocked",
+                    "=comment=drop blocked",
+                    "=place-before=*2"
+                ],
+                vec!["/ip/firewall/filter/move", ".tag=2", "=numbers=*A"],
+                vec!["/ip/firewall/filter/disable", ".tag=3", "=.id=*A"],
+                vec!["/ip/firewall/filter/enable", ".tag=4", "=.id=*A"],
+                vec![
+                    "/ip/firewall/filter/print",
+                    ".tag=5",
+                    "=.proplist=.id,bytes,packets"
+                ],
+            ]
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Models of the `/interface` menu
+use crate::{
+    error::Error,
+    prelude::{MikrotikDevice, ParsedMessage, ReplayPolicy},
+    resource::{ResourceStream, RouterOsResource},
+    values::{named_enum, text_value, ItemId, MacAddress, ParseValueError, RosDateTime, RosList},
+};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+named_enum! {
+    /// Kind of an interface as given by its `type` attribute
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum InterfaceType {
+        Ether = "ether",
+        Bridge = "bridge",
+        Vlan = "vlan",
+        Bonding = "bond",
+        Loopback = "loopback",
+        /// Wireless interface of the legacy wireless package
+        Wlan = "wlan",
+        /// Wireless interface of the wifi package of RouterOS 7
+        Wifi = "wifi",
+        Wireguard = "wg",
+        Vrrp = "vrrp",
+        Eoip = "eoip",
+        Gre = "gre-tunnel",
+        Ipip = "ipip-tunnel",
+        Vxlan = "vxlan",
+        PppoeClient = "pppoe-out",
+        PppoeServer = "pppoe-in",
+        L2tpClient = "l2tp-out",
+        SstpClient = "sstp-out",
+        OvpnClient = "ovpn-out",
+        Lte = "lte",
+        /// Interface of a container
+        Veth = "veth";
+        /// Any type without own variant
+        Other(Box<str>),
+    }
+}
+
+/// Maximum transmission unit, `auto` on bridges and some tunnels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Mtu {
+    Auto,
+    Bytes(u16),
+}
+
+impl FromStr for Mtu {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Mtu::Auto),
+            s => s
+                .parse()
+                .map(Mtu::Bytes)
+                .map_err(|_| ParseValueError::new("mtu", s)),
+        }
+    }
+}
+
+impl Display for Mtu {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mtu::Auto => f.write_str("auto"),
+            Mtu::Bytes(bytes) => Display::fmt(bytes, f),
+        }
+    }
+}
+
+text_value!(Mtu);
+
+/// An interface of any type with its traffic counters, `/interface`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/interface")]
+pub struct Interface {
+    pub id: ItemId,
+    pub name: String,
+    #[ros(read_only)]
+    pub default_name: Option<String>,
+    #[ros(rename = "type", read_only)]
+    pub interface_type: InterfaceType,
+    pub mtu: Option<Mtu>,
+    #[ros(read_only)]
+    pub actual_mtu: Option<u16>,
+    #[ros(read_only)]
+    pub l2mtu: Option<u16>,
+    #[ros(read_only)]
+    pub mac_address: Option<MacAddress>,
+    #[ros(read_only)]
+    pub running: bool,
+    pub disabled: bool,
+    #[ros(read_only)]
+    pub slave: Option<bool>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub rx_byte: Option<u64>,
+    #[ros(read_only)]
+    pub tx_byte: Option<u64>,
+    #[ros(read_only)]
+    pub rx_packet: Option<u64>,
+    #[ros(read_only)]
+    pub tx_packet: Option<u64>,
+    #[ros(read_only)]
+    pub rx_drop: Option<u64>,
+    #[ros(read_only)]
+    pub tx_drop: Option<u64>,
+    #[ros(read_only)]
+    pub tx_queue_drop: Option<u64>,
+    #[ros(read_only)]
+    pub rx_error: Option<u64>,
+    #[ros(read_only)]
+    pub tx_error: Option<u64>,
+    #[ros(read_only)]
+    pub link_downs: Option<u32>,
+    #[ros(read_only)]
+    pub last_link_up_time: Option<RosDateTime>,
+    pub comment: Option<String>,
+}
+
+/// Current throughput of an interface, reported every second by `/interface/monitor-traffic`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/interface/monitor-traffic")]
+pub struct TrafficSample {
+    pub name: String,
+    pub rx_bits_per_second: u64,
+    pub tx_bits_per_second: u64,
+    pub rx_packets_per_second: u64,
+    pub tx_packets_per_second: u64,
+    pub rx_drops_per_second: Option<u64>,
+    pub tx_drops_per_second: Option<u64>,
+    pub rx_errors_per_second: Option<u64>,
+    pub tx_errors_per_second: Option<u64>,
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Reads all interfaces with their counters
+    pub async fn list_interfaces(&self) -> Result<Vec<Interface>, Error> {
+        self.print().await
+    }
+    /// Streams one [`TrafficSample`] per interface and second until the stream is dropped.
+    /// The monitor is restarted after a reconnect.
+    pub async fn monitor_traffic<S: Into<String>>(
+        &self,
+        names: impl IntoIterator<Item = S>,
+    ) -> Result<ResourceStream<TrafficSample>, Error> {
+        let names = RosList(names.into_iter().map(Into::into).collect::<Vec<String>>());
+        self.stream_resources(b"/interface/monitor-traffic", |cb| {
+            cb.value_attribute(b"interface", &names)
+                .no_timeout()
+                .replay_policy(ReplayPolicy::Resubscribe)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{resource::ReadableResource, simple::SimpleResult, testing::MockRouter};
+    use tokio::io::duplex;
+    use tokio_stream::StreamExt;
+
+    const ETHER: &[(&[u8], Option<&[u8]>)] = &[
+        (b".id", Some(b"*1")),
+        (b"name", Some(b"ether1")),
+        (b"default-name", Some(b"ether1")),
+        (b"type", Some(b"ether")),
+        (b"mtu", Some(b"1500")),
+        (b"actual-mtu", Some(b"1500")),
+        (b"l2mtu", Some(b"1598")),
+        (b"max-l2mtu", Some(b"4074")),
+        (b"mac-address", Some(b"48:8F:5A:01:02:03")),
+        (b"last-link-up-time", Some(b"2024-11-02 08:15:44")),
+        (b"link-downs", Some(b"2")),
+        (b"rx-byte", Some(b"81521987316")),
+        (b"tx-byte", Some(b"9185477325")),
+        (b"rx-packet", Some(b"63044588")),
+        (b"tx-packet", Some(b"30129341")),
+        (b"rx-drop", Some(b"0")),
+        (b"tx-drop", Some(b"0")),
+        (b"tx-queue-drop", Some(b"0")),
+        (b"rx-error", Some(b"0")),
+        (b"tx-error", Some(b"0")),
+        (b"fp-rx-byte", Some(b"81521987316")),
+        (b"running", Some(b"true")),
+        (b"slave", Some(b"true")),
+        (b"disabled", Some(b"false")),
+    ];
+
+    const BRIDGE: &[(&[u8], Option<&[u8]>)] = &[
+        (b".id", Some(b"*A")),
+        (b"name", Some(b"bridge")),
+        (b"type", Some(b"bridge")),
+        (b"mtu", Some(b"auto")),
+        (b"actual-mtu", Some(b"1500")),
+        (b"mac-address", Some(b"48:8F:5A:01:02:04")),
+        (b"running", Some(b"true")),
+        (b"disabled", Some(b"false")),
+        (b"comment", Some(b"lan")),
+    ];
+
+    const WIREGUARD: &[(&[u8], Option<&[u8]>)] = &[
+        (b".id", Some(b"*F")),
+        (b"name", Some(b"wg-office")),
+        (b"type", Some(b"wg")),
+        (b"mtu", Some(b"1420")),
+        (b"actual-mtu", Some(b"1420")),
+        (b"rx-byte", Some(b"0")),
+        (b"tx-byte", Some(b"0")),
+        (b"running", Some(b"false")),
+        (b"disabled", Some(b"true")),
+    ];
+
+    #[test]
+    fn test_interface_type() {
+        assert_eq!("wg".parse(), Ok(InterfaceType::Wireguard));
+        assert_eq!("pppoe-out".parse(), Ok(InterfaceType::PppoeClient));
+        assert_eq!(
+            "6to4-tunnel".parse(),
+            Ok(InterfaceType::Other(Box::from("6to4-tunnel")))
+        );
+        assert_eq!(InterfaceType::Vlan.to_string(), "vlan");
+        assert_eq!(
+            InterfaceType::Other(Box::from("ppp-in")).to_string(),
+            "ppp-in"
+        );
+        for (name, interface_type) in InterfaceType::NAMES {
+            assert_eq!(interface_type.to_string(), *name);
+            assert_eq!(name.parse(), Ok(interface_type.clone()));
+        }
+    }
+
+    #[test]
+    fn test_parse_recorded() {
+        let ether = Interface::from_sentence(ETHER).unwrap();
+        assert_eq!(ether.id, ItemId(1));
+        assert_eq!(ether.interface_type, InterfaceType::Ether);
+        assert_eq!(ether.mtu, Some(Mtu::Bytes(1500)));
+        assert_eq!(
+            ether.mac_address,
+            Some(MacAddress([0x48, 0x8f, 0x5a, 0x01, 0x02, 0x03]))
+        );
+        assert_eq!(ether.rx_byte, Some(81521987316));
+        assert_eq!(ether.link_downs, Some(2));
+        assert!(ether.running);
+        assert_eq!(ether.slave, Some(true));
+
+        let bridge = Interface::from_sentence(BRIDGE).unwrap();
+        assert_eq!(bridge.mtu, Some(Mtu::Auto));
+        assert_eq!(bridge.comment.as_deref(), Some("lan"));
+        assert_eq!(bridge.rx_byte, None);
+
+        let wireguard = Interface::from_sentence(WIREGUARD).unwrap();
+        assert_eq!(wireguard.interface_type, InterfaceType::Wireguard);
+        assert_eq!(wireguard.mac_address, None);
+        assert!(wireguard.disabled);
+    }
+
+    #[tokio::test]
+    async fn test_list_interfaces() {
+        let (client, server) = duplex(8192);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            for sentence in [ETHER, BRIDGE, WIREGUARD] {
+                router.reply_recorded(1, sentence).await;
+            }
+            router.reply(&["!done", ".tag=1"]).await;
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let interfaces = device.list_interfaces().await.unwrap();
+        let names: Vec<_> = interfaces
+            .iter()
+            .map(|interface| (interface.name.as_str(), &interface.interface_type))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("ether1", &InterfaceType::Ether),
+                ("bridge", &InterfaceType::Bridge),
+                ("wg-office", &InterfaceType::Wireguard)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_monitor_traffic() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let monitor = router.read_sentence().await.unwrap();
+            for (name, rx) in [("ether1", "1200"), ("ether2", "800")] {
+                router
+                    .reply(&[
+                        "!re",
+                        ".tag=1",
+                        &format!("=name={name}"),
+                        "=rx-packets-per-second=3",
+                        &format!("=rx-bits-per-second={rx}"),
+                        "=fp-rx-packets-per-second=3",
+                        "=rx-drops-per-second=0",
+                        "=rx-errors-per-second=0",
+                        "=tx-packets-per-second=2",
+                        "=tx-bits-per-second=2400",
+                        "=tx-drops-per-second=0",
+                        "=tx-queue-drops-per-second=0",
+                        "=tx-errors-per-second=0",
+                    ])
+                    .await;
+            }
+            let cancel = router.read_sentence().await.unwrap();
+            router
+                .reply(&["!trap", ".tag=1", "=category=2", "=message=interrupted"])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            (monitor, cancel)
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let mut samples = device.monitor_traffic(["ether1", "ether2"]).await.unwrap();
+        let first = samples.next().await.unwrap().unwrap();
+        assert_eq!(
+            first,
+            TrafficSample {
+                name: "ether1".to_string(),
+                rx_bits_per_second: 1200,
+                tx_bits_per_second: 2400,
+                rx_packets_per_second: 3,
+                tx_packets_per_second: 2,
+                rx_drops_per_second: Some(0),
+                tx_drops_per_second: Some(0),
+                rx_errors_per_second: Some(0),
+                tx_errors_per_second: Some(0),
+            }
+        );
+        let second = samples.next().await.unwrap().unwrap();
+        assert_eq!(second.name, "ether2");
+        assert_eq!(second.rx_bits_per_second, 800);
+        samples.cancel().await;
+
+        let (monitor, cancel) = router.await.unwrap();
+        assert_eq!(
+            monitor,
+            [
+                "/interface/monitor-traffic",
+                ".tag=1",
+                "=interface=ether1,ether2"
+            ]
+        );
+        assert_eq!(cancel, ["/cancel", ".tag=2", "=tag=1"]);
+    }
+}
@@ -0,0 +1,549 @@
+//! Models of the `/ip` and `/ipv6` menus for addresses, routes and neighbors
+use crate::{
+    error::Error,
+    prelude::{CommandBuilder, MikrotikDevice, ParsedMessage},
+    protocol::command::QueryOperator,
+    resource::{ReadableResource, ResourceError, RouterOsResource},
+    values::{IpNet, ItemId, MacAddress, RosDuration, RosList},
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Routing table of RouterOS 7 used if none is given
+pub const MAIN_TABLE: &str = "main";
+
+/// IPv4 address of an interface, `/ip/address`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/address")]
+pub struct IpAddress {
+    pub id: Option<ItemId>,
+    /// Address of the interface with the prefix length of its network, e.g. `192.168.88.1/24`
+    pub address: IpNet,
+    #[ros(read_only)]
+    pub network: Option<Ipv4Addr>,
+    pub interface: String,
+    #[ros(read_only)]
+    pub actual_interface: Option<String>,
+    pub disabled: Option<bool>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub invalid: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl IpAddress {
+    /// An address to add to an interface
+    pub fn new(address: IpNet, interface: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            address,
+            network: None,
+            interface: interface.into(),
+            actual_interface: None,
+            disabled: None,
+            dynamic: None,
+            invalid: None,
+            comment: None,
+        }
+    }
+}
+
+/// IPv6 address of an interface, `/ipv6/address`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/ipv6/address")]
+pub struct Ipv6Address {
+    pub id: Option<ItemId>,
+    pub address: IpNet,
+    pub interface: String,
+    #[ros(read_only)]
+    pub actual_interface: Option<String>,
+    /// Prefix is announced by router advertisements
+    pub advertise: Option<bool>,
+    /// Host part is generated from the mac address of the interface
+    pub eui_64: Option<bool>,
+    /// Prefix is taken from the given pool
+    pub from_pool: Option<String>,
+    pub no_dad: Option<bool>,
+    pub disabled: Option<bool>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub invalid: Option<bool>,
+    #[ros(read_only)]
+    pub link_local: Option<bool>,
+    #[ros(read_only)]
+    pub global: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl Ipv6Address {
+    /// An address to add to an interface
+    pub fn new(address: IpNet, interface: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            address,
+            interface: interface.into(),
+            actual_interface: None,
+            advertise: None,
+            eui_64: None,
+            from_pool: None,
+            no_dad: None,
+            disabled: None,
+            dynamic: None,
+            invalid: None,
+            link_local: None,
+            global: None,
+            comment: None,
+        }
+    }
+}
+
+/// IPv4 route, `/ip/route`, see [`Ipv6Route`] for the routes of `/ipv6/route`.
+///
+/// RouterOS 7 selects the table by `routing-table`, RouterOS 6 by `routing-mark`. Only the
+/// attribute known by the device may be written, [`Route::table`] reads either.
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/route")]
+pub struct Route {
+    pub id: Option<ItemId>,
+    pub dst_address: IpNet,
+    /// Address, interface or `address%interface` of the next hop
+    pub gateway: Option<String>,
+    #[ros(read_only)]
+    pub immediate_gw: Option<String>,
+    pub distance: Option<u8>,
+    pub scope: Option<u8>,
+    pub target_scope: Option<u8>,
+    pub pref_src: Option<IpAddr>,
+    pub routing_table: Option<String>,
+    pub routing_mark: Option<String>,
+    /// Route drops the traffic, RouterOS 7
+    pub blackhole: Option<bool>,
+    /// `unicast`, `blackhole`, `prohibit` or `unreachable`, RouterOS 6
+    #[ros(rename = "type", read_only)]
+    pub route_type: Option<String>,
+    #[ros(read_only)]
+    pub active: Option<bool>,
+    #[ros(read_only)]
+    pub dynamic: Option<bool>,
+    #[ros(read_only)]
+    pub r#static: Option<bool>,
+    #[ros(read_only)]
+    pub connect: Option<bool>,
+    pub disabled: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl Route {
+    /// A static route to add, forwarding `dst_address` to `gateway`
+    pub fn new(dst_address: IpNet, gateway: impl Into<String>) -> Self {
+        Self {
+            gateway: Some(gateway.into()),
+            ..Self::empty(dst_address)
+        }
+    }
+
+    /// A static route to add, dropping the traffic to `dst_address` (RouterOS 7)
+    pub fn blackhole(dst_address: IpNet) -> Self {
+        Self {
+            blackhole: Some(true),
+            ..Self::empty(dst_address)
+        }
+    }
+
+    fn empty(dst_address: IpNet) -> Self {
+        Self {
+            id: None,
+            dst_address,
+            gateway: None,
+            immediate_gw: None,
+            distance: None,
+            scope: None,
+            target_scope: None,
+            pref_src: None,
+            routing_table: None,
+            routing_mark: None,
+            blackhole: None,
+            route_type: None,
+            active: None,
+            dynamic: None,
+            r#static: None,
+            connect: None,
+            disabled: None,
+            comment: None,
+        }
+    }
+
+    /// Sets the routing table on RouterOS 7
+    pub fn in_table(mut self, table: impl Into<String>) -> Self {
+        self.routing_table = Some(table.into());
+        self
+    }
+
+    /// Routing table of the route, `routing-table` of RouterOS 7 or `routing-mark` of RouterOS 6
+    pub fn table(&self) -> &str {
+        self.routing_table
+            .as_deref()
+            .or(self.routing_mark.as_deref())
+            .unwrap_or(MAIN_TABLE)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active == Some(true)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic == Some(true)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.r#static == Some(true)
+    }
+
+    /// Drops its traffic, by the flag of RouterOS 7 or the type of RouterOS 6
+    pub fn is_blackhole(&self) -> bool {
+        self.blackhole == Some(true) || self.route_type.as_deref() == Some("blackhole")
+    }
+}
+
+/// IPv6 route, `/ipv6/route`, which has the same attributes as [`Route`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ipv6Route(pub Route);
+
+impl ReadableResource for Ipv6Route {
+    const PATH: &'static str = "/ipv6/route";
+    const PROPERTIES: &'static [&'static str] = Route::PROPERTIES;
+
+    fn from_sentence(sentence: &[(&[u8], Option<&[u8]>)]) -> Result<Self, ResourceError> {
+        Route::from_sentence(sentence).map(Self)
+    }
+}
+
+impl RouterOsResource for Ipv6Route {
+    fn write_attributes(&self, builder: CommandBuilder) -> CommandBuilder {
+        self.0.write_attributes(builder)
+    }
+}
+
+/// Device found by a discovery protocol, `/ip/neighbor`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/ip/neighbor")]
+pub struct Neighbor {
+    #[ros(read_only)]
+    pub id: Option<ItemId>,
+    /// Local interfaces the neighbor is seen on
+    #[ros(read_only)]
+    pub interface: RosList<String>,
+    #[ros(read_only)]
+    pub address: Option<IpAddr>,
+    #[ros(read_only)]
+    pub address4: Option<Ipv4Addr>,
+    #[ros(read_only)]
+    pub address6: Option<Ipv6Addr>,
+    #[ros(read_only)]
+    pub mac_address: Option<MacAddress>,
+    #[ros(read_only)]
+    pub identity: Option<String>,
+    #[ros(read_only)]
+    pub platform: Option<String>,
+    #[ros(read_only)]
+    pub version: Option<String>,
+    #[ros(read_only)]
+    pub board: Option<String>,
+    #[ros(read_only)]
+    pub uptime: Option<RosDuration>,
+    /// Interface of the neighbor facing this device
+    #[ros(read_only)]
+    pub interface_name: Option<String>,
+    /// Protocols which found the neighbor, e.g. `cdp,lldp,mndp`
+    #[ros(read_only)]
+    pub discovered_by: Option<RosList<String>>,
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Reads the IPv4 addresses of all interfaces
+    pub async fn ip_addresses(&self) -> Result<Vec<IpAddress>, Error> {
+        self.print().await
+    }
+    /// Reads the IPv6 addresses of all interfaces
+    pub async fn ipv6_addresses(&self) -> Result<Vec<Ipv6Address>, Error> {
+        self.print().await
+    }
+    /// Reads the IPv4 routes of all tables
+    pub async fn routes(&self) -> Result<Vec<Route>, Error> {
+        self.print().await
+    }
+    /// Reads the IPv4 routes whose [`Route::table`] is `table`, selected by `routing-table` on
+    /// RouterOS 7 and by `routing-mark` on RouterOS 6, where the main table has no mark
+    pub async fn routes_in_table(&self, table: &str) -> Result<Vec<Route>, Error> {
+        self.print_filtered(|cb| {
+            let cb = cb
+                .query_equal(b"routing-table", table.as_bytes())
+                .query_equal(b"routing-mark", table.as_bytes())
+                .query_operations([QueryOperator::Or].into_iter());
+            if table == MAIN_TABLE {
+                cb.query_not_present(b"routing-table")
+                    .query_not_present(b"routing-mark")
+                    .query_operations([QueryOperator::And, QueryOperator::Or].into_iter())
+            } else {
+                cb
+            }
+        })
+        .await
+    }
+    /// Reads the IPv6 routes of all tables
+    pub async fn ipv6_routes(&self) -> Result<Vec<Ipv6Route>, Error> {
+        self.print().await
+    }
+    /// Reads the neighbors found by the discovery protocols
+    pub async fn neighbors(&self) -> Result<Vec<Neighbor>, Error> {
+        self.print().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simple::SimpleResult, testing::MockRouter};
+    use tokio::io::duplex;
+
+    #[test]
+    fn test_parse_addresses() {
+        let address = IpAddress::from_sentence(&[
+            (b".id", Some(b"*2")),
+            (b"address", Some(b"192.168.88.1/24")),
+            (b"network", Some(b"192.168.88.0")),
+            (b"interface", Some(b"bridge")),
+            (b"actual-interface", Some(b"bridge")),
+            (b"invalid", Some(b"false")),
+            (b"dynamic", Some(b"false")),
+            (b"disabled", Some(b"false")),
+            (b"comment", Some(b"defconf")),
+        ])
+        .unwrap();
+        assert_eq!(address.id, Some(ItemId(2)));
+        assert_eq!(address.address.to_string(), "192.168.88.1/24");
+        assert_eq!(address.network, Some(Ipv4Addr::new(192, 168, 88, 0)));
+        assert_eq!(address.comment.as_deref(), Some("defconf"));
+
+        let address = Ipv6Address::from_sentence(&[
+            (b".id", Some(b"*5")),
+            (b"address", Some(b"fe80::4a8f:5aff:fe01:203/64")),
+            (b"from-pool", Some(b"")),
+            (b"interface", Some(b"bridge")),
+            (b"actual-interface", Some(b"bridge")),
+            (b"eui-64", Some(b"false")),
+            (b"advertise", Some(b"false")),
+            (b"no-dad", Some(b"false")),
+            (b"invalid", Some(b"false")),
+            (b"dynamic", Some(b"true")),
+            (b"link-local", Some(b"true")),
+            (b"disabled", Some(b"false")),
+        ])
+        .unwrap();
+        assert_eq!(address.address.prefix_len(), 64);
+        assert_eq!(address.link_local, Some(true));
+        assert_eq!(address.eui_64, Some(false));
+    }
+
+    #[test]
+    fn test_parse_routes() {
+        let route = Route::from_sentence(&[
+            (b".id", Some(b"*80000001")),
+            (b"dst-address", Some(b"0.0.0.0/0")),
+            (b"routing-table", Some(b"main")),
+            (b"pref-src", Some(b"")),
+            (b"gateway", Some(b"10.0.0.1")),
+            (b"immediate-gw", Some(b"10.0.0.1%ether1")),
+            (b"distance", Some(b"1")),
+            (b"scope", Some(b"30")),
+            (b"target-scope", Some(b"10")),
+            (b"vrf-interface", Some(b"ether1")),
+            (b"suppress-hw-offload", Some(b"false")),
+            (b"dynamic", Some(b"false")),
+            (b"inactive", Some(b"false")),
+            (b"active", Some(b"true")),
+            (b"static", Some(b"true")),
+            (b"disabled", Some(b"false")),
+        ])
+        .unwrap();
+        assert_eq!(route.id, Some(ItemId(0x80000001)));
+        assert_eq!(route.pref_src, None);
+        assert_eq!(route.table(), "main");
+        assert!(route.is_active() && route.is_static() && !route.is_dynamic());
+        assert!(!route.is_blackhole());
+
+        let route = Route::from_sentence(&[
+            (b".id", Some(b"*3")),
+            (b"dst-address", Some(b"10.66.0.0/16")),
+            (b"blackhole", Some(b"true")),
+            (b"routing-table", Some(b"vpn")),
+            (b"distance", Some(b"1")),
+            (b"active", Some(b"true")),
+            (b"static", Some(b"true")),
+        ])
+        .unwrap();
+        assert!(route.is_blackhole());
+        assert_eq!(route.table(), "vpn");
+
+        let route = Route::from_sentence(&[
+            (b".id", Some(b"*4")),
+            (b"dst-address", Some(b"10.66.0.0/16")),
+            (b"type", Some(b"blackhole")),
+            (b"routing-mark", Some(b"vpn")),
+            (b"distance", Some(b"1")),
+            (b"active", Some(b"true")),
+            (b"static", Some(b"true")),
+        ])
+        .unwrap();
+        assert!(route.is_blackhole());
+        assert_eq!(route.table(), "vpn");
+        assert_eq!(route.gateway, None);
+    }
+
+    #[test]
+    fn test_parse_neighbor() {
+        let neighbor = Neighbor::from_sentence(&[
+            (b".id", Some(b"*1")),
+            (b"interface", Some(b"ether2,bridge")),
+            (b"address", Some(b"192.168.88.2")),
+            (b"address4", Some(b"192.168.88.2")),
+            (b"address6", Some(b"fe80::ba69:f4ff:fe01:203")),
+            (b"mac-address", Some(b"B8:69:F4:01:02:03")),
+            (b"identity", Some(b"switch")),
+            (b"platform", Some(b"MikroTik")),
+            (b"version", Some(b"7.16.1 (stable) Oct/10/2024 14:03:32")),
+            (b"unpack", Some(b"none")),
+            (b"age", Some(b"42s")),
+            (b"uptime", Some(b"3w2d1h")),
+            (b"board", Some(b"CRS326-24G-2S+")),
+            (b"interface-name", Some(b"bridge/ether1")),
+            (b"system-description", Some(b"MikroTik RouterOS 7.16.1")),
+            (b"discovered-by", Some(b"cdp,lldp,mndp")),
+        ])
+        .unwrap();
+        assert_eq!(neighbor.interface.0, ["ether2", "bridge"]);
+        assert_eq!(neighbor.address4, Some(Ipv4Addr::new(192, 168, 88, 2)));
+        assert_eq!(neighbor.identity.as_deref(), Some("switch"));
+        assert_eq!(
+            neighbor.discovered_by.map(|protocols| protocols.0),
+            Some(vec![
+                "cdp".to_string(),
+                "lldp".to_string(),
+                "mndp".to_string()
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_helpers() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let print = router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*7",
+                    "=dst-address=10.1.0.0/16",
+                    "=routing-table=vpn",
+                    "=gateway=10.0.0.2",
+                    "=active=true",
+                    "=static=true",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            let add = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=2", "=ret=*8"]).await;
+            let add_address = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=3", "=ret=*9"]).await;
+            let print_main = router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=4",
+                    "=.id=*1",
+                    "=dst-address=0.0.0.0/0",
+                    "=gateway=10.0.0.1",
+                    "=active=true",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=4"]).await;
+            let print_ipv6 = router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=5",
+                    "=.id=*2",
+                    "=dst-address=2001:db8::/32",
+                    "=gateway=fe80::1%ether1",
+                    "=routing-table=main",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=5"]).await;
+            [print, add, add_address, print_main, print_ipv6]
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let routes = device.routes_in_table("vpn").await.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].gateway.as_deref(), Some("10.0.0.2"));
+        let id = device
+            .add(&Route::blackhole("10.66.0.0/16".parse().unwrap()).in_table("vpn"))
+            .await
+            .unwrap();
+        assert_eq!(id, ItemId(8));
+        let id = device
+            .add(&IpAddress::new("10.0.0.1/24".parse().unwrap(), "ether1"))
+            .await
+            .unwrap();
+        assert_eq!(id, ItemId(9));
+        // a RouterOS 6 route of the main table has no routing-mark
+        let routes = device.routes_in_table(MAIN_TABLE).await.unwrap();
+        assert_eq!(routes[0].table(), MAIN_TABLE);
+        let routes = device.ipv6_routes().await.unwrap();
+        assert_eq!(routes[0].0.dst_address.to_string(), "2001:db8::/32");
+
+        let [print, add, add_address, print_main, print_ipv6] = router.await.unwrap();
+        assert_eq!(print[0], "/ip/route/print");
+        assert_eq!(
+            print[3..],
+            ["?routing-table=vpn", "?routing-mark=vpn", "?#|"]
+        );
+        assert_eq!(
+            print_main[3..],
+            [
+                "?routing-table=main",
+                "?routing-mark=main",
+                "?#|",
+                "?-routing-table",
+                "?-routing-mark",
+                "?#&|"
+            ]
+        );
+        assert_eq!(print_ipv6[0], "/ipv6/route/print");
+        assert_eq!(
+            add,
+            [
+                "/ip/route/add",
+                ".tag=2",
+                "=dst-address=10.66.0.0/16",
+                "=routing-table=vpn",
+                "=blackhole=yes"
+            ]
+        );
+        assert_eq!(
+            add_address,
+            [
+                "/ip/address/add",
+                ".tag=3",
+                "=address=10.0.0.1/24",
+                "=interface=ether1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Typed models of RouterOS menus, read and written with the helpers of [`crate::resource`]
+pub mod address_list;
+pub mod dhcp;
+pub mod firewall;
+pub mod interface;
+pub mod ip;
+pub mod system;
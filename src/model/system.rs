@@ -0,0 +1,163 @@
+//! Models of the `/system` menus
+use crate::{
+    error::Error,
+    prelude::{MikrotikDevice, ParsedMessage},
+    resource::{ReadableResource, ResourceError, RouterOsResource},
+    values::{RosBytes, RosDateTime, RosDuration},
+};
+
+/// Usage and identification of the device hardware and software, `/system/resource`
+#[derive(Debug, Clone, PartialEq, RouterOsResource)]
+#[ros(path = "/system/resource")]
+pub struct SystemResource {
+    pub uptime: RosDuration,
+    /// Version and channel, e.g. `7.16.1 (stable)`
+    pub version: String,
+    pub build_time: Option<RosDateTime>,
+    pub factory_software: Option<String>,
+    pub free_memory: RosBytes,
+    pub total_memory: RosBytes,
+    pub cpu: String,
+    pub cpu_count: u16,
+    /// Current frequency in MHz, missing on some virtual machines
+    pub cpu_frequency: Option<u32>,
+    /// Load in percent
+    pub cpu_load: u8,
+    pub free_hdd_space: Option<RosBytes>,
+    pub total_hdd_space: Option<RosBytes>,
+    pub write_sect_since_reboot: Option<u64>,
+    pub write_sect_total: Option<u64>,
+    /// Bad blocks of the storage in percent
+    pub bad_blocks: Option<f32>,
+    pub architecture_name: String,
+    pub board_name: String,
+    pub platform: String,
+}
+
+impl SystemResource {
+    /// The version without its release channel, e.g. `7.16.1`
+    pub fn version_number(&self) -> &str {
+        self.version
+            .split_once(' ')
+            .map_or(self.version.as_str(), |(number, _)| number)
+    }
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Reads the [`SystemResource`] of the device
+    pub async fn system_resource(&self) -> Result<SystemResource, Error> {
+        self.print::<SystemResource>()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(Error::Resource(ResourceError::NoItem(SystemResource::PATH)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simple::SimpleResult, testing::MockRouter};
+    use std::time::Duration;
+    use tokio::io::duplex;
+
+    const ROUTEROS_7: &[(&[u8], Option<&[u8]>)] = &[
+        (b"uptime", Some(b"2w1d4h12m3s")),
+        (b"version", Some(b"7.16.1 (stable)")),
+        (b"build-time", Some(b"2024-10-10 14:03:32")),
+        (b"factory-software", Some(b"6.44.6")),
+        (b"free-memory", Some(b"158556160")),
+        (b"total-memory", Some(b"268435456")),
+        (b"cpu", Some(b"ARM")),
+        (b"cpu-count", Some(b"4")),
+        (b"cpu-frequency", Some(b"716")),
+        (b"cpu-load", Some(b"3")),
+        (b"free-hdd-space", Some(b"1540096")),
+        (b"total-hdd-space", Some(b"16777216")),
+        (b"write-sect-since-reboot", Some(b"4521")),
+        (b"write-sect-total", Some(b"845213")),
+        (b"bad-blocks", Some(b"0")),
+        (b"architecture-name", Some(b"arm")),
+        (b"board-name", Some(b"hAP ac^2")),
+        (b"platform", Some(b"MikroTik")),
+    ];
+
+    const ROUTEROS_6_CHR: &[(&[u8], Option<&[u8]>)] = &[
+        (b"uptime", Some(b"3d04:05:06")),
+        (b"version", Some(b"6.49.10 (long-term)")),
+        (b"build-time", Some(b"Aug/31/2023 11:20:30")),
+        (b"free-memory", Some(b"206123008")),
+        (b"total-memory", Some(b"268435456")),
+        (b"cpu", Some(b"QEMU Virtual CPU version 2.5+")),
+        (b"cpu-count", Some(b"1")),
+        (b"cpu-load", Some(b"0")),
+        (b"free-hdd-space", Some(b"85999616")),
+        (b"total-hdd-space", Some(b"134217728")),
+        (b"architecture-name", Some(b"x86_64")),
+        (b"board-name", Some(b"CHR")),
+        (b"platform", Some(b"MikroTik")),
+    ];
+
+    #[test]
+    fn test_parse_recorded() {
+        let resource = SystemResource::from_sentence(ROUTEROS_7).unwrap();
+        assert_eq!(
+            resource.uptime.0,
+            Duration::from_secs(15 * 24 * 3600 + 4 * 3600 + 12 * 60 + 3)
+        );
+        assert_eq!(resource.version_number(), "7.16.1");
+        assert_eq!(
+            resource.build_time.map(|time| time.to_string()).as_deref(),
+            Some("2024-10-10 14:03:32")
+        );
+        assert_eq!(resource.free_memory, RosBytes(158556160));
+        assert_eq!(resource.cpu_count, 4);
+        assert_eq!(resource.cpu_frequency, Some(716));
+        assert_eq!(resource.cpu_load, 3);
+        assert_eq!(resource.bad_blocks, Some(0.0));
+        assert_eq!(resource.board_name, "hAP ac^2");
+
+        let resource = SystemResource::from_sentence(ROUTEROS_6_CHR).unwrap();
+        assert_eq!(
+            resource.uptime.0,
+            Duration::from_secs(3 * 24 * 3600 + 4 * 3600 + 5 * 60 + 6)
+        );
+        assert_eq!(resource.version_number(), "6.49.10");
+        assert_eq!(
+            resource.build_time.map(|time| time.to_string()).as_deref(),
+            Some("2023-08-31 11:20:30")
+        );
+        assert_eq!(resource.cpu_frequency, None);
+        assert_eq!(resource.write_sect_total, None);
+        assert_eq!(resource.architecture_name, "x86_64");
+    }
+
+    #[tokio::test]
+    async fn test_system_resource() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let print = router.read_sentence().await.unwrap();
+            router.reply_recorded(1, ROUTEROS_7).await;
+            router.reply(&["!done", ".tag=1"]).await;
+            router.read_sentence().await.unwrap();
+            router.reply(&["!empty", ".tag=2"]).await;
+            router.reply(&["!done", ".tag=2"]).await;
+            print
+        });
+        let device =
+            MikrotikDevice::<SimpleResult>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let resource = device.system_resource().await.unwrap();
+        assert_eq!(resource, SystemResource::from_sentence(ROUTEROS_7).unwrap());
+        assert!(matches!(
+            device.system_resource().await,
+            Err(Error::Resource(ResourceError::NoItem("/system/resource")))
+        ));
+        let print = router.await.unwrap();
+        assert_eq!(print[0], "/system/resource/print");
+        assert!(print[2].starts_with("=.proplist=uptime,version,build-time,"));
+    }
+}
@@ -1,4 +1,10 @@
-use crate::protocol::{WordContent, WordSequenceItem};
+use crate::{
+    protocol::{error::ProtocolError, WordContent, WordSequenceItem},
+    values::ToAttribute,
+};
+use encoding_rs::mem::encode_latin1_lossy;
+use md5::{Digest, Md5};
+use std::{fmt::Write, time::Duration};
 
 /// Builds MikroTik router commands using a fluid API.
 ///
@@ -15,6 +21,36 @@ use crate::protocol::{WordContent, WordSequenceItem};
 pub struct CommandBuilder {
     tag: u16,
     cmd: CommandBuffer,
+    replay_policy: ReplayPolicy,
+    timeout: CommandTimeout,
+}
+
+/// Defines what happens to a running command when the connection to the device is lost
+/// and a reconnecting device reestablished it.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum ReplayPolicy {
+    /// The command fails with [`crate::error::Error::ConnectionClosed`]
+    #[default]
+    Fail,
+    /// The command is sent again if it did not deliver any reply yet, otherwise it fails.
+    /// Suitable for idempotent commands like `print`.
+    Retry,
+    /// The command is always sent again and its stream stays open.
+    /// Suitable for subscriptions like `listen` or `print follow`.
+    Resubscribe,
+}
+
+/// Time a command may run until it is completed by the device. An expired command is cancelled
+/// and its stream receives a [`crate::error::Error::Timeout`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum CommandTimeout {
+    /// The command timeout of the connection applies
+    #[default]
+    Default,
+    /// The command may run forever, e.g. for `listen`
+    Never,
+    /// The command expires after the given duration
+    After(Duration),
 }
 
 impl CommandBuilder {
@@ -25,7 +61,12 @@ impl CommandBuilder {
         let tag_str: WordSequenceItem = string.as_bytes().into();
         cmd.write_word(command.into());
         cmd.write_word([b".tag=".into(), tag_str]);
-        Self { tag, cmd }
+        Self {
+            tag,
+            cmd,
+            replay_policy: ReplayPolicy::default(),
+            timeout: CommandTimeout::default(),
+        }
     }
 
     /// Builds a login command with the provided username and optional password.
@@ -59,11 +100,64 @@ impl CommandBuilder {
         .build()
     }
 
-    /// Builds a command to cancel a specific running command identified by `tag`.
+    /// Builds the response to the login challenge of RouterOS before 6.43.
+    ///
+    /// These versions reply to the initial login with `=ret=<challenge>` and expect
+    /// `00` followed by the hex encoded MD5 hash of a zero byte, the password and the challenge.
     ///
     /// # Arguments
     ///
-    /// * `tag` - The tag of the command to be canceled.
+    /// * `tag` - tag of the login command
+    /// * `username` - The username for the login command.
+    /// * `password` - An optional password for the login command.
+    /// * `challenge` - hex encoded challenge sent by the device
+    ///
+    /// # Returns
+    ///
+    /// A `Command` which represents the second step of the login, or an error if the challenge is not valid hex.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mikrotik_api::prelude::CommandBuilder;
+    /// let login_cmd = CommandBuilder::legacy_login(0, b"admin", Some(b"password"), b"ebddd18303a54111e2dea05a92ab46b4");
+    /// assert!(login_cmd.is_ok());
+    /// ```
+    pub fn legacy_login<'u, U: Into<WordSequenceItem<'u>>>(
+        tag: u16,
+        username: U,
+        password: Option<&[u8]>,
+        challenge: &[u8],
+    ) -> Result<Command, ProtocolError> {
+        if challenge.len() % 2 != 0 {
+            return Err(ProtocolError::InvalidChallenge(Box::from(challenge)));
+        }
+        let mut hasher = Md5::new();
+        hasher.update([0]);
+        hasher.update(password.unwrap_or_default());
+        for digits in challenge.chunks(2) {
+            let byte = std::str::from_utf8(digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| ProtocolError::InvalidChallenge(Box::from(challenge)))?;
+            hasher.update([byte]);
+        }
+        let mut response = String::from("00");
+        for byte in hasher.finalize() {
+            let _ = write!(response, "{byte:02x}");
+        }
+        Ok(Self::new(tag, b"/login")
+            .attribute(b"name", username)
+            .attribute(b"response", response.as_bytes())
+            .build())
+    }
+
+    /// Builds a command to cancel a specific running command identified by `target`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag of the cancel command itself, must differ from `target`.
+    /// * `target` - The tag of the command to be canceled.
     ///
     /// # Returns
     ///
@@ -73,11 +167,11 @@ impl CommandBuilder {
     ///
     /// ```rust
     /// use mikrotik_api::prelude::CommandBuilder;
-    /// let cancel_cmd = CommandBuilder::cancel(1234);
+    /// let cancel_cmd = CommandBuilder::cancel(1235, 1234);
     /// ```
-    pub fn cancel(tag: u16) -> Command {
+    pub fn cancel(tag: u16, target: u16) -> Command {
         Self::new(tag, b"/cancel")
-            .attribute(b"tag=", tag.to_string().as_bytes())
+            .attribute(b"tag", target.to_string().as_bytes())
             .build()
     }
 
@@ -92,13 +186,13 @@ impl CommandBuilder {
     ///
     /// The builder with the attribute added, allowing for method chaining.
     pub fn attribute<'k, 'v, K: Into<WordSequenceItem<'k>>, V: Into<WordSequenceItem<'v>>>(
-        self,
+        mut self,
         key: K,
         value: V,
     ) -> Self {
-        let Self { tag, mut cmd, .. } = self;
-        cmd.write_word([b"=".into(), key.into(), b"=".into(), value.into()]);
-        CommandBuilder { tag, cmd }
+        self.cmd
+            .write_word([b"=".into(), key.into(), b"=".into(), value.into()]);
+        self
     }
 
     /// Adds a flag attribute to the command being built.
@@ -110,10 +204,89 @@ impl CommandBuilder {
     /// # Returns
     ///
     /// The builder with the attribute added, allowing for method chaining.
-    pub fn flag_attribute<'k, K: Into<WordSequenceItem<'k>>>(self, key: K) -> Self {
-        let Self { tag, mut cmd, .. } = self;
-        cmd.write_word([b"=".into(), key.into(), b"=".into()]);
-        CommandBuilder { tag, cmd }
+    pub fn flag_attribute<'k, K: Into<WordSequenceItem<'k>>>(mut self, key: K) -> Self {
+        self.cmd.write_word([b"=".into(), key.into(), b"=".into()]);
+        self
+    }
+
+    /// Adds an attribute converted from a typed value, e.g. a `bool` is written as `yes`/`no`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The attribute's key.
+    /// * `value` - The attribute's value, the attribute is omitted if it converts to `None`.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the attribute added, allowing for method chaining.
+    pub fn value_attribute<'k, K: Into<WordSequenceItem<'k>>, V: ToAttribute + ?Sized>(
+        self,
+        key: K,
+        value: &V,
+    ) -> Self {
+        match value.to_attribute() {
+            Some(value) => self.attribute(key, encode_latin1_lossy(&value).as_ref()),
+            None => self,
+        }
+    }
+
+    /// Adds the fields of a struct or map as attributes, see [`crate::ser`] for how values are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - struct or map holding the attributes, fields with a value of `None` are skipped
+    ///
+    /// # Returns
+    ///
+    /// The builder with the attributes added, allowing for method chaining, or an error if `value`
+    /// cannot be written as attributes.
+    #[cfg(feature = "serde")]
+    pub fn attributes_from<T: serde::Serialize + ?Sized>(
+        mut self,
+        value: &T,
+    ) -> Result<Self, crate::ser::SerializeError> {
+        for (key, value) in crate::ser::to_attributes(value)? {
+            self = self.attribute(key.as_bytes(), value.as_slice());
+        }
+        Ok(self)
+    }
+
+    /// Defines how the command is handled when the connection is reestablished by a reconnecting device.
+    ///
+    /// # Arguments
+    ///
+    /// * `replay_policy` - policy applied to this command, [`ReplayPolicy::Fail`] by default
+    ///
+    /// # Returns
+    ///
+    /// The builder with the policy set, allowing for method chaining.
+    pub fn replay_policy(mut self, replay_policy: ReplayPolicy) -> Self {
+        self.replay_policy = replay_policy;
+        self
+    }
+
+    /// Overrides the command timeout of the connection for this command.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - time until the command is cancelled if it is not completed
+    ///
+    /// # Returns
+    ///
+    /// The builder with the timeout set, allowing for method chaining.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = CommandTimeout::After(timeout);
+        self
+    }
+
+    /// Disables the command timeout of the connection for this command, e.g. for `listen`.
+    ///
+    /// # Returns
+    ///
+    /// The builder without timeout, allowing for method chaining.
+    pub fn no_timeout(mut self) -> Self {
+        self.timeout = CommandTimeout::Never;
+        self
     }
 
     /// Adds a query to the command being built.
@@ -226,12 +399,19 @@ impl CommandBuilder {
     ///
     /// A `Command` instance ready for execution.
     pub fn build(self) -> Command {
-        let Self { tag, mut cmd, .. } = self;
+        let Self {
+            tag,
+            mut cmd,
+            replay_policy,
+            timeout,
+        } = self;
         // Terminate the command
         cmd.write_len(0);
         Command {
             tag,
             data: cmd.0.into_boxed_slice(),
+            replay_policy,
+            timeout,
         }
     }
 }
@@ -254,15 +434,19 @@ pub struct Command {
     pub tag: u16,
     /// The data of the command.
     pub data: Box<[u8]>,
+    /// Handling of the command after a reconnect.
+    pub replay_policy: ReplayPolicy,
+    /// Time the command may run.
+    pub timeout: CommandTimeout,
 }
 
 #[derive(Default, Clone)]
-struct CommandBuffer(Vec<u8>);
+pub(crate) struct CommandBuffer(pub(crate) Vec<u8>);
 impl CommandBuffer {
     fn write_str(&mut self, str_buff: &[u8]) {
         self.0.extend_from_slice(str_buff);
     }
-    fn write_len(&mut self, len: u32) {
+    pub(crate) fn write_len(&mut self, len: u32) {
         match len {
             0x00..=0x7F => self.write_str(&[len as u8]),
             0x80..=0x3FFF => {
@@ -292,7 +476,7 @@ impl CommandBuffer {
             }
         }
     }
-    fn write_word(&mut self, w: impl WordContent) {
+    pub(crate) fn write_word(&mut self, w: impl WordContent) {
         self.write_len(w.byte_count() as u32);
         w.write_to_buffer(&mut self.0);
     }
@@ -382,12 +566,103 @@ mod tests {
             .contains("password=password"));
     }
 
+    #[test]
+    fn test_command_builder_legacy_login() {
+        let command = CommandBuilder::legacy_login(
+            0,
+            b"admin",
+            Some(b"secret"),
+            b"ebddd18303a54111e2dea05a92ab46b4",
+        )
+        .unwrap();
+        let expected_data: &[u8] = &[
+            &b"\x06/login"[..],
+            b"\x06.tag=0",
+            b"\x0b=name=admin",
+            b"\x2c=response=007319531c22b6b85e160d6ac355c1df2e",
+            b"\x00",
+        ]
+        .concat();
+        assert_eq!(command.data.as_ref(), expected_data);
+
+        let command =
+            CommandBuilder::legacy_login(0, b"admin", None, b"ebddd18303a54111e2dea05a92ab46b4")
+                .unwrap();
+        assert!(str::from_utf8(&command.data)
+            .unwrap()
+            .contains("=response=001ea726ed53ae38520c8334f82d44c9f2"));
+
+        assert!(matches!(
+            CommandBuilder::legacy_login(0, b"admin", None, b"ebd"),
+            Err(ProtocolError::InvalidChallenge(_))
+        ));
+        assert!(matches!(
+            CommandBuilder::legacy_login(0, b"admin", None, b"zz"),
+            Err(ProtocolError::InvalidChallenge(_))
+        ));
+    }
+
     #[test]
     fn test_command_builder_cancel() {
-        let command = CommandBuilder::cancel(1234);
+        let command = CommandBuilder::cancel(7, 1234);
+
+        assert_eq!(command.tag, 7);
+        let expected_data: &[u8] = &[
+            &b"\x07/cancel"[..],
+            b"\x06.tag=7",
+            b"\x09=tag=1234",
+            b"\x00",
+        ]
+        .concat();
+        assert_eq!(command.data.as_ref(), expected_data);
+
+        let command = CommandBuilder::cancel(300, 2);
+        let expected_data: &[u8] =
+            &[&b"\x07/cancel"[..], b"\x08.tag=300", b"\x06=tag=2", b"\x00"].concat();
+        assert_eq!(command.data.as_ref(), expected_data);
+    }
 
-        assert!(str::from_utf8(&command.data).unwrap().contains("/cancel"));
-        assert!(str::from_utf8(&command.data).unwrap().contains("tag=1234"));
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_command_builder_attributes_from() {
+        #[derive(serde::Serialize)]
+        struct Identity {
+            name: &'static str,
+            comment: Option<&'static str>,
+        }
+        let command = CommandBuilder::new(1, b"/system/identity/set")
+            .attributes_from(&Identity {
+                name: "router",
+                comment: None,
+            })
+            .unwrap()
+            .build();
+        let expected_data: &[u8] = &[
+            &b"\x14/system/identity/set"[..],
+            b"\x06.tag=1",
+            b"\x0c=name=router",
+            b"\x00",
+        ]
+        .concat();
+        assert_eq!(command.data.as_ref(), expected_data);
+    }
+
+    #[test]
+    fn test_command_builder_value_attribute() {
+        let command = CommandBuilder::new(1, b"/ip/address/set")
+            .value_attribute(b"disabled", &true)
+            .value_attribute(b"comment", &None::<String>)
+            .value_attribute(b"network", "10.0.0.0")
+            .build();
+        let expected_data: &[u8] = &[
+            &b"\x0f/ip/address/set"[..],
+            b"\x06.tag=1",
+            b"\x0d=disabled=yes",
+            b"\x11=network=10.0.0.0",
+            b"\x00",
+        ]
+        .concat();
+        assert_eq!(command.data.as_ref(), expected_data);
     }
 
     #[test]
@@ -29,6 +29,8 @@ pub enum ProtocolError {
     MissingCategoryInTrap,
     #[error("Missing message field in trap")]
     MissingMessageInTrap,
+    #[error("Invalid login challenge: {content}",content=String::from_utf8_lossy(.0))]
+    InvalidChallenge(Box<[u8]>),
 }
 /// Types of words that can be missing from a response.
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -46,7 +46,7 @@ impl<const N: usize> WordContent for &[u8; N] {
 
 impl WordContent for &str {
     fn byte_count(&self) -> usize {
-        self.bytes().len()
+        self.len()
     }
     fn write_to_buffer(&self, buffer: &mut Vec<u8>) {
         assert!(
@@ -77,7 +77,7 @@ impl<'a, const N: usize> From<&'a [&'a [u8]; N]> for WordSequenceItem<'a> {
     fn from(value: &'a [&'a [u8]; N]) -> Self {
         WordSequenceItem::Sequence(
             value
-                .into_iter()
+                .iter()
                 .copied()
                 .map(Cow::Borrowed)
                 .map(WordSequenceItem::Data)
@@ -100,6 +100,14 @@ impl<'a> From<Cow<'a, [u8]>> for WordSequenceItem<'a> {
         WordSequenceItem::Data(value)
     }
 }
+impl WordSequenceItem<'_> {
+    /// Copies the content into an owned buffer
+    pub(crate) fn to_bytes(&self) -> Box<[u8]> {
+        let mut buffer = Vec::with_capacity(self.byte_count());
+        self.write_to_buffer(&mut buffer);
+        buffer.into_boxed_slice()
+    }
+}
 impl WordContent for WordSequenceItem<'_> {
     fn byte_count(&self) -> usize {
         match self {
@@ -147,7 +147,7 @@ impl<'a> Iterator for WordIterator<'a> {
 }
 
 /// Returns the length and the number of bytes read.
-fn read_length(data: &[u8]) -> Result<(u32, usize), ProtocolError> {
+pub(crate) fn read_length(data: &[u8]) -> Result<(u32, usize), ProtocolError> {
     let mut c: u32 = data[0] as u32;
     if c & 0x80 == 0x00 {
         Ok((c, 1))
@@ -155,14 +155,14 @@ fn read_length(data: &[u8]) -> Result<(u32, usize), ProtocolError> {
         c &= !0xC0;
         c <<= 8;
         c += data[1] as u32;
-        return Ok((c, 2));
+        Ok((c, 2))
     } else if c & 0xE0 == 0xC0 {
         c &= !0xE0;
         c <<= 8;
         c += data[1] as u32;
         c <<= 8;
         c += data[2] as u32;
-        return Ok((c, 3));
+        Ok((c, 3))
     } else if c & 0xF0 == 0xE0 {
         c &= !0xF0;
         c <<= 8;
@@ -171,7 +171,7 @@ fn read_length(data: &[u8]) -> Result<(u32, usize), ProtocolError> {
         c += data[2] as u32;
         c <<= 8;
         c += data[3] as u32;
-        return Ok((c, 4));
+        Ok((c, 4))
     } else if c & 0xF8 == 0xF0 {
         c = data[1] as u32;
         c <<= 8;
@@ -180,13 +180,13 @@ fn read_length(data: &[u8]) -> Result<(u32, usize), ProtocolError> {
         c += data[3] as u32;
         c <<= 8;
         c += data[4] as u32;
-        return Ok((c, 5));
+        Ok((c, 5))
     } else {
         Err(ProtocolError::PrefixLength)
     }
 }
 
-pub fn next_sentence(data: &[u8]) -> Result<(Vec<Word>, usize), ProtocolError> {
+pub fn next_sentence(data: &[u8]) -> Result<(Vec<Word<'_>>, usize), ProtocolError> {
     let mut iterator = WordIterator { data, idx: 0 };
     let mut sentence = Vec::new();
     while let Some(item) = iterator.next() {
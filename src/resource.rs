@@ -0,0 +1,571 @@
+//! Typed items of a RouterOS menu.
+//!
+//! A type implementing [`RouterOsResource`], usually by `#[derive(RouterOsResource)]`, can be
+//! read and written with [`MikrotikDevice::print`], [`MikrotikDevice::add`],
+//! [`MikrotikDevice::set`] and [`MikrotikDevice::remove`] on a device of any [`ParsedMessage`],
+//! or be the message of a device as [`Resource`]. A type implementing only
+//! [`ReadableResource`] is a read-only view of a menu, e.g. some of its attributes, and can only be printed.
+//!
+//! # Examples
+//! ```no_run
+//! # async fn example(device: mikrotik_api::prelude::MikrotikDevice<mikrotik_api::simple::SimpleResult>) -> Result<(), mikrotik_api::error::Error> {
+//! use mikrotik_api::{resource::RouterOsResource, values::ItemId};
+//!
+//! #[derive(Debug, RouterOsResource)]
+//! #[ros(path = "/ip/address")]
+//! struct Address {
+//!     id: Option<ItemId>,
+//!     address: String,
+//!     interface: String,
+//!     #[ros(read_only)]
+//!     dynamic: Option<bool>,
+//!     disabled: Option<bool>,
+//!     comment: Option<String>,
+//! }
+//!
+//! for address in device.print::<Address>().await? {
+//!     println!("{address:?}");
+//! }
+//! let id = device
+//!     .add(&Address {
+//!         id: None,
+//!         address: "10.0.0.1/24".to_string(),
+//!         interface: "ether1".to_string(),
+//!         dynamic: None,
+//!         disabled: None,
+//!         comment: Some("uplink".to_string()),
+//!     })
+//!     .await?;
+//! device.remove::<Address>(id).await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{
+    device::{done_ret, SentenceReply},
+    error::Error,
+    prelude::{CommandBuilder, CommandStream, MikrotikDevice, ParsedMessage},
+    protocol::{
+        word::{TrapCategory, TrapResult},
+        WordSequenceItem,
+    },
+    values::{FromAttribute, ItemId, ParseValueError},
+};
+use encoding_rs::mem::decode_latin1;
+pub use mikrotik_api_derive::RouterOsResource;
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use thiserror::Error;
+use tokio_stream::{Stream, StreamExt};
+
+/// Items of a menu, converted from replies
+pub trait ReadableResource: Sized + Send + 'static {
+    /// Path of the menu, e.g. `/ip/address`
+    const PATH: &'static str;
+    /// Attributes read from replies, requested as `.proplist` by [`MikrotikDevice::print`]
+    const PROPERTIES: &'static [&'static str];
+    /// Converts the attributes of a reply
+    fn from_sentence(sentence: &[(&[u8], Option<&[u8]>)]) -> Result<Self, ResourceError>;
+}
+
+/// Items of a menu, converted from replies and into attributes of commands
+pub trait RouterOsResource: ReadableResource {
+    /// Adds the writable attributes to an `add` or `set` command, `.id` is never written
+    fn write_attributes(&self, builder: CommandBuilder) -> CommandBuilder;
+}
+
+/// A reply does not match the resource
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ResourceError {
+    #[error("Missing attribute {0}")]
+    Missing(&'static str),
+    #[error("Attribute {attribute}: {error}")]
+    Invalid {
+        attribute: &'static str,
+        error: ParseValueError,
+    },
+    #[error("No item in {0}")]
+    NoItem(&'static str),
+}
+
+/// Converts the value of an attribute, `None` if it is missing in the reply.
+/// Used by the code of `#[derive(RouterOsResource)]`.
+#[doc(hidden)]
+pub fn parse_attribute<T: FromAttribute>(
+    attribute: &'static str,
+    value: Option<Option<&[u8]>>,
+) -> Result<T, ResourceError> {
+    match value {
+        Some(value) => T::from_attribute(&decode_latin1(value.unwrap_or_default()))
+            .map_err(|error| ResourceError::Invalid { attribute, error }),
+        None => T::missing().ok_or(ResourceError::Missing(attribute)),
+    }
+}
+
+/// Replies converted into `T`, the other events as in [`SimpleResult`](crate::simple::SimpleResult)
+#[derive(Debug, Clone)]
+pub enum Resource<T> {
+    Item(T),
+    /// A reply could not be converted
+    Invalid(ResourceError),
+    Error(Error),
+    Trap {
+        category: Option<TrapCategory>,
+        message: Box<str>,
+    },
+    /// The command has no results
+    Empty,
+    /// The command completed, `ret` holds its return value, e.g. the id of an added item
+    Done {
+        ret: Option<Box<str>>,
+    },
+}
+
+impl<T: ReadableResource> ParsedMessage for Resource<T> {
+    type Context = ();
+
+    fn parse_message(sentence: &[(&[u8], Option<&[u8]>)], _: &Self::Context) -> Self {
+        match T::from_sentence(sentence) {
+            Ok(item) => Resource::Item(item),
+            Err(e) => Resource::Invalid(e),
+        }
+    }
+
+    fn process_error(error: &Error, _: &Self::Context) -> Self {
+        Resource::Error(error.clone())
+    }
+
+    fn process_trap(TrapResult { category, message }: TrapResult, _: &Self::Context) -> Self {
+        Resource::Trap {
+            category,
+            message: Box::from(decode_latin1(message)),
+        }
+    }
+
+    fn process_empty(_: &Self::Context) -> Option<Self> {
+        Some(Resource::Empty)
+    }
+
+    fn process_done(attributes: &[(&[u8], Option<&[u8]>)], _: &Self::Context) -> Option<Self> {
+        Some(Resource::Done {
+            ret: done_ret(attributes),
+        })
+    }
+}
+
+/// Replies of a command converted into `T`, a trap ends the stream with [`Error::Trap`].
+/// A replay after a reconnect is reported by [`Error::Resubscribed`] before the items of the replay.
+///
+/// Dropping the stream before the command is completed cancels the command on the device.
+#[derive(Debug)]
+pub struct ResourceStream<T> {
+    stream: CommandStream<Result<SentenceReply, Error>>,
+    item: PhantomData<fn() -> T>,
+}
+
+impl<T> ResourceStream<T> {
+    /// Tag of the command on the connection
+    pub fn tag(&self) -> u16 {
+        self.stream.tag()
+    }
+
+    /// Cancels the command and waits until the device confirmed it, remaining responses are discarded
+    pub async fn cancel(self) {
+        self.stream.cancel().await
+    }
+}
+
+impl<T: ReadableResource> Stream for ResourceStream<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let sentence = match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+            Some(Ok(SentenceReply::Sentence { attributes, .. })) => attributes,
+            Some(Ok(SentenceReply::Resubscribed)) => {
+                let tag = self.stream.tag();
+                return Poll::Ready(Some(Err(Error::Resubscribed { tag })));
+            }
+            Some(Err(error)) => return Poll::Ready(Some(Err(error))),
+            None => return Poll::Ready(None),
+        };
+        let attributes: Vec<_> = sentence
+            .iter()
+            .map(|(key, value)| (key.as_ref(), value.as_deref()))
+            .collect();
+        Poll::Ready(Some(T::from_sentence(&attributes).map_err(Error::from)))
+    }
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Sends a command and converts its replies into `T`, e.g. for `listen` or `monitor` commands
+    pub async fn stream_resources<
+        T: ReadableResource,
+        F: FnOnce(CommandBuilder) -> CommandBuilder,
+    >(
+        &self,
+        command: impl Into<WordSequenceItem<'_>>,
+        command_builder: F,
+    ) -> Result<ResourceStream<T>, Error> {
+        Ok(ResourceStream {
+            stream: self
+                .send_sentences_command(command, command_builder)
+                .await?,
+            item: PhantomData,
+        })
+    }
+    /// Prints all items of the menu of `T`, requesting only its [`RouterOsResource::PROPERTIES`]
+    pub async fn print<T: ReadableResource>(&self) -> Result<Vec<T>, Error> {
+        self.print_filtered(|cb| cb).await
+    }
+    /// Prints the items of the menu of `T` matching the queries added by `filter`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use mikrotik_api::{prelude::*, resource::RouterOsResource};
+    /// # #[derive(RouterOsResource)]
+    /// # #[ros(path = "/interface")]
+    /// # struct Interface { name: String }
+    /// # async fn example(device: MikrotikDevice<mikrotik_api::simple::SimpleResult>) -> Result<(), mikrotik_api::error::Error> {
+    /// let running: Vec<Interface> = device
+    ///     .print_filtered(|cb| cb.query_equal(b"running", b"true"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn print_filtered<
+        T: ReadableResource,
+        F: FnOnce(CommandBuilder) -> CommandBuilder,
+    >(
+        &self,
+        filter: F,
+    ) -> Result<Vec<T>, Error> {
+        let proplist = T::PROPERTIES.join(",");
+        let mut stream = self
+            .stream_resources::<T, _>(format!("{}/print", T::PATH).as_bytes(), |cb| {
+                filter(cb.attribute(b".proplist", proplist.as_bytes()))
+            })
+            .await?;
+        let mut items = Vec::new();
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(item) => items.push(item),
+                // The replayed print starts over
+                Err(Error::Resubscribed { .. }) => items.clear(),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(items)
+    }
+    /// Adds an item to the menu of `T` and returns its id
+    pub async fn add<T: RouterOsResource>(&self, item: &T) -> Result<ItemId, Error> {
+        self.add_with(item, |cb| cb).await
+    }
+    /// Adds an item with further attributes added by `extra`, e.g. its position
+    pub(crate) async fn add_with<
+        T: RouterOsResource,
+        F: FnOnce(CommandBuilder) -> CommandBuilder,
+    >(
+        &self,
+        item: &T,
+        extra: F,
+    ) -> Result<ItemId, Error> {
+        let ret = self
+            .call(format!("{}/add", T::PATH).as_bytes(), |cb| {
+                extra(item.write_attributes(cb))
+            })
+            .await?
+            .ok_or(ResourceError::Missing("ret"))?;
+        Ok(
+            ItemId::from_attribute(&ret).map_err(|error| ResourceError::Invalid {
+                attribute: "ret",
+                error,
+            })?,
+        )
+    }
+    /// Writes the attributes of `item` to the item with the given id, `None` values are left unchanged
+    pub async fn set<T: RouterOsResource>(&self, id: ItemId, item: &T) -> Result<(), Error> {
+        self.call(format!("{}/set", T::PATH).as_bytes(), |cb| {
+            item.write_attributes(cb.value_attribute(b".id", &id))
+        })
+        .await?;
+        Ok(())
+    }
+    /// Removes the item with the given id from the menu of `T`
+    pub async fn remove<T: RouterOsResource>(&self, id: ItemId) -> Result<(), Error> {
+        self.call(format!("{}/remove", T::PATH).as_bytes(), |cb| {
+            cb.value_attribute(b".id", &id)
+        })
+        .await?;
+        Ok(())
+    }
+    /// Enables the item with the given id in the menu of `T`
+    pub async fn enable<T: RouterOsResource>(&self, id: ItemId) -> Result<(), Error> {
+        self.call(format!("{}/enable", T::PATH).as_bytes(), |cb| {
+            cb.value_attribute(b".id", &id)
+        })
+        .await?;
+        Ok(())
+    }
+    /// Disables the item with the given id in the menu of `T`
+    pub async fn disable<T: RouterOsResource>(&self, id: ItemId) -> Result<(), Error> {
+        self.call(format!("{}/disable", T::PATH).as_bytes(), |cb| {
+            cb.value_attribute(b".id", &id)
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::{ReconnectOptions, ReplayPolicy},
+        simple::SimpleResult,
+        testing::MockRouter,
+    };
+    use std::time::Duration;
+    use tokio::{io::duplex, sync::mpsc};
+
+    #[derive(Debug, Clone, PartialEq, RouterOsResource)]
+    #[ros(path = "/ip/address")]
+    struct Address {
+        id: Option<ItemId>,
+        address: String,
+        #[ros(rename = "interface")]
+        interface_name: String,
+        #[ros(read_only)]
+        dynamic: Option<bool>,
+        disabled: Option<bool>,
+        comment: Option<String>,
+        #[ros(skip)]
+        checked: bool,
+    }
+
+    #[test]
+    fn test_derive() {
+        assert_eq!(Address::PATH, "/ip/address");
+        assert_eq!(
+            Address::PROPERTIES,
+            [
+                ".id",
+                "address",
+                "interface",
+                "dynamic",
+                "disabled",
+                "comment"
+            ]
+        );
+        let address = Address::from_sentence(&[
+            (b".id", Some(b"*3")),
+            (b"address", Some(b"10.0.0.1/24")),
+            (b"network", Some(b"10.0.0.0")),
+            (b"interface", Some(b"ether1")),
+            (b"dynamic", Some(b"false")),
+            (b"comment", Some(b"")),
+        ])
+        .unwrap();
+        assert_eq!(
+            address,
+            Address {
+                id: Some(ItemId(3)),
+                address: "10.0.0.1/24".to_string(),
+                interface_name: "ether1".to_string(),
+                dynamic: Some(false),
+                disabled: None,
+                comment: Some(String::new()),
+                checked: false,
+            }
+        );
+        assert_eq!(
+            Address::from_sentence(&[(b"address", Some(b"10.0.0.1/24"))]),
+            Err(ResourceError::Missing("interface"))
+        );
+        assert!(matches!(
+            Address::from_sentence(&[
+                (b"address", Some(b"10.0.0.1/24")),
+                (b"interface", Some(b"ether1")),
+                (b"disabled", Some(b"maybe")),
+            ]),
+            Err(ResourceError::Invalid {
+                attribute: "disabled",
+                ..
+            })
+        ));
+
+        let command = address
+            .write_attributes(CommandBuilder::new(1, b"/ip/address/add"))
+            .build();
+        let expected_data: &[u8] = &[
+            &b"\x0f/ip/address/add"[..],
+            b"\x06.tag=1",
+            b"\x14=address=10.0.0.1/24",
+            b"\x11=interface=ether1",
+            b"\x09=comment=",
+            b"\x00",
+        ]
+        .concat();
+        assert_eq!(command.data.as_ref(), expected_data);
+    }
+
+    #[tokio::test]
+    async fn test_device_helpers() {
+        let (client, server) = duplex(4096);
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            let print = router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*1",
+                    "=address=192.168.88.1/24",
+                    "=interface=bridge",
+                    "=dynamic=false",
+                    "=disabled=false",
+                ])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+            let add = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=2", "=ret=*2"]).await;
+            let set = router.read_sentence().await.unwrap();
+            router
+                .reply(&["!trap", ".tag=3", "=message=no such item"])
+                .await;
+            router.reply(&["!done", ".tag=3"]).await;
+            let remove = router.read_sentence().await.unwrap();
+            router.reply(&["!done", ".tag=4"]).await;
+            [print, add, set, remove]
+        });
+        let device = MikrotikDevice::<crate::simple::SimpleResult>::connect_stream(
+            "test",
+            client,
+            b"admin",
+            Some(b""),
+        )
+        .await
+        .unwrap();
+        let addresses = device.print::<Address>().await.unwrap();
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].id, Some(ItemId(1)));
+        let address = Address {
+            id: None,
+            address: "10.0.0.1/24".to_string(),
+            interface_name: "ether1".to_string(),
+            dynamic: None,
+            disabled: Some(true),
+            comment: None,
+            checked: false,
+        };
+        assert_eq!(device.add(&address).await.unwrap(), ItemId(2));
+        assert!(matches!(
+            device.set(ItemId(5), &address).await,
+            Err(Error::Trap { message, .. }) if message.as_ref() == "no such item"
+        ));
+        device.remove::<Address>(ItemId(2)).await.unwrap();
+
+        let [print, add, set, remove] = router.await.unwrap();
+        assert_eq!(
+            print,
+            [
+                "/ip/address/print",
+                ".tag=1",
+                "=.proplist=.id,address,interface,dynamic,disabled,comment"
+            ]
+        );
+        assert_eq!(
+            add,
+            [
+                "/ip/address/add",
+                ".tag=2",
+                "=address=10.0.0.1/24",
+                "=interface=ether1",
+                "=disabled=yes"
+            ]
+        );
+        assert_eq!(
+            set,
+            [
+                "/ip/address/set",
+                ".tag=3",
+                "=.id=*5",
+                "=address=10.0.0.1/24",
+                "=interface=ether1",
+                "=disabled=yes"
+            ]
+        );
+        assert_eq!(remove, ["/ip/address/remove", ".tag=4", "=.id=*2"]);
+    }
+
+    #[tokio::test]
+    async fn test_resubscribed() {
+        let (connection_tx, mut connection_rx) = mpsc::channel(2);
+        let (first_client, first_server) = duplex(1024);
+        let (second_client, second_server) = duplex(1024);
+        connection_tx.send(first_client).await.unwrap();
+        connection_tx.send(second_client).await.unwrap();
+        let connector = move || {
+            let stream = connection_rx.try_recv();
+            async move {
+                stream
+                    .map(tokio::io::split)
+                    .map_err(|_| std::io::ErrorKind::ConnectionRefused.into())
+            }
+        };
+        let address = [
+            "!re",
+            ".tag=1",
+            "=.id=*1",
+            "=address=10.0.0.1/24",
+            "=interface=ether1",
+        ];
+        let router = tokio::spawn(async move {
+            let mut router = MockRouter::new(first_server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router.reply(&address).await;
+            drop(router);
+
+            let mut router = MockRouter::new(second_server);
+            router.accept_login().await;
+            let replayed = router.read_sentence().await.unwrap();
+            router.reply(&address).await;
+            router.reply(&["!done", ".tag=1"]).await;
+            replayed
+        });
+        let device = MikrotikDevice::<SimpleResult>::connect_reconnecting_with(
+            "test",
+            connector,
+            b"admin",
+            Some(b""),
+            ReconnectOptions::default().initial_backoff(Duration::from_millis(1)),
+        )
+        .await
+        .unwrap();
+        let stream = device
+            .stream_resources::<Address, _>(b"/ip/address/print", |cb| {
+                cb.replay_policy(ReplayPolicy::Resubscribe)
+            })
+            .await
+            .unwrap();
+        let replies: Vec<_> = stream.collect().await;
+        assert!(matches!(
+            replies.as_slice(),
+            [
+                Ok(Address {
+                    id: Some(ItemId(1)),
+                    ..
+                }),
+                Err(Error::Resubscribed { tag: 1 }),
+                Ok(Address {
+                    id: Some(ItemId(1)),
+                    ..
+                }),
+            ]
+        ));
+        assert_eq!(router.await.unwrap(), ["/ip/address/print", ".tag=1"]);
+    }
+}
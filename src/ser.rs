@@ -0,0 +1,709 @@
+//! Serialize a struct or map into the attributes of a command with serde.
+//!
+//! This is the counterpart of [`crate::de`], so the same type can be used to read items and
+//! to `add` or `set` them. RouterOS conventions are applied:
+//!
+//! * a field `id` is written as `.id`, other keys as they are, e.g. `#[serde(rename = ".about")]`
+//! * booleans are written as `yes`/`no`
+//! * `None` omits the attribute, `()` writes an attribute with an empty value
+//! * sequences are joined with `,`
+//! * a [`RosDuration`](crate::values::RosDuration) is written in RouterOS notation, e.g. `1h30m`,
+//!   a plain [`Duration`](std::time::Duration) is rejected as nested struct
+//! * unit enum variants are written as their name
+//!
+//! # Examples
+//! ```
+//! use mikrotik_api::ser::to_attributes;
+//! use serde::Serialize;
+//! use mikrotik_api::values::RosDuration;
+//! use std::time::Duration;
+//!
+//! #[derive(Serialize)]
+//! #[serde(rename_all = "kebab-case")]
+//! struct AddressListEntry {
+//!     list: String,
+//!     address: String,
+//!     timeout: Option<RosDuration>,
+//!     disabled: bool,
+//!     comment: Option<String>,
+//! }
+//!
+//! let attributes = to_attributes(&AddressListEntry {
+//!     list: "blocked".to_string(),
+//!     address: "10.0.0.1".to_string(),
+//!     timeout: Some(RosDuration(Duration::from_secs(3600))),
+//!     disabled: false,
+//!     comment: None,
+//! })
+//! .unwrap();
+//! assert_eq!(
+//!     attributes,
+//!     [
+//!         ("list".to_string(), b"blocked".to_vec()),
+//!         ("address".to_string(), b"10.0.0.1".to_vec()),
+//!         ("timeout".to_string(), b"1h".to_vec()),
+//!         ("disabled".to_string(), b"no".to_vec()),
+//!     ]
+//! );
+//! ```
+use encoding_rs::mem::encode_latin1_lossy;
+use serde::{
+    ser::{
+        Error as _, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple,
+        SerializeTupleStruct,
+    },
+    Serialize, Serializer,
+};
+use std::fmt::Display;
+use thiserror::Error;
+
+/// A value cannot be written as attributes
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Cannot serialize attributes: {0}")]
+pub struct SerializeError(Box<str>);
+
+impl serde::ser::Error for SerializeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerializeError(msg.to_string().into_boxed_str())
+    }
+}
+
+/// Flattens a struct or map into the key and value of each attribute
+pub fn to_attributes<T: Serialize + ?Sized>(
+    value: &T,
+) -> Result<Vec<(String, Vec<u8>)>, SerializeError> {
+    value.serialize(AttributesSerializer)
+}
+
+fn unsupported(kind: &str) -> SerializeError {
+    SerializeError::custom(format_args!("{kind} cannot be written as attributes"))
+}
+
+/// Writes a struct or map as list of attributes
+struct AttributesSerializer;
+
+impl Serializer for AttributesSerializer {
+    type Ok = Vec<(String, Vec<u8>)>;
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<Self::Ok, SerializeError>;
+    type SerializeTuple = Impossible<Self::Ok, SerializeError>;
+    type SerializeTupleStruct = Impossible<Self::Ok, SerializeError>;
+    type SerializeTupleVariant = Impossible<Self::Ok, SerializeError>;
+    type SerializeMap = AttributeCollector;
+    type SerializeStruct = AttributeCollector;
+    type SerializeStructVariant = Impossible<Self::Ok, SerializeError>;
+
+    fn serialize_bool(self, _: bool) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a bool"))
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_char(self, _: char) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a char"))
+    }
+
+    fn serialize_str(self, _: &str) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a string"))
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a byte array"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(AttributeCollector {
+            attributes: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(AttributeCollector {
+            attributes: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(unsupported("an enum"))
+    }
+}
+
+struct AttributeCollector {
+    attributes: Vec<(String, Vec<u8>)>,
+    key: Option<String>,
+}
+
+impl AttributeCollector {
+    fn push<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerializeError> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            let key = if key == "id" { ".id" } else { key };
+            self.attributes.push((key.to_string(), value));
+        }
+        Ok(())
+    }
+}
+
+impl SerializeStruct for AttributeCollector {
+    type Ok = Vec<(String, Vec<u8>)>;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.attributes)
+    }
+}
+
+impl SerializeMap for AttributeCollector {
+    type Ok = Vec<(String, Vec<u8>)>;
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        let key = key
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| SerializeError::custom("a key must have a value"))?;
+        self.key = Some(String::from_utf8_lossy(&key).into_owned());
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerializeError::custom("value written before key"))?;
+        self.push(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.attributes)
+    }
+}
+
+/// Writes a single attribute value, `None` if the attribute is omitted
+struct ValueSerializer;
+
+impl ValueSerializer {
+    fn text(value: impl Display) -> Result<Option<Vec<u8>>, SerializeError> {
+        Ok(Some(value.to_string().into_bytes()))
+    }
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<Vec<u8>>;
+    type Error = SerializeError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = Impossible<Self::Ok, SerializeError>;
+    type SerializeMap = Impossible<Self::Ok, SerializeError>;
+    type SerializeStruct = Impossible<Self::Ok, SerializeError>;
+    type SerializeStructVariant = Impossible<Self::Ok, SerializeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Self::text(if v { "yes" } else { "no" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Self::text(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(encode_latin1_lossy(v).into_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Vec::new()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an enum variant with data"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(unsupported("an enum variant with data"))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(unsupported("a nested map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(unsupported("a nested struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(unsupported("an enum variant with data"))
+    }
+}
+
+/// Joins the items of a sequence with `,`
+struct ListSerializer {
+    items: Vec<Vec<u8>>,
+}
+
+impl SerializeSeq for ListSerializer {
+    type Ok = Option<Vec<u8>>;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        if let Some(item) = value.serialize(ValueSerializer)? {
+            self.items.push(item);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(self.items.join(&b',')))
+    }
+}
+
+impl SerializeTuple for ListSerializer {
+    type Ok = Option<Vec<u8>>;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for ListSerializer {
+    type Ok = Option<Vec<u8>>;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        de::from_sentence,
+        values::{ItemId, RosDuration},
+    };
+    use serde::Deserialize;
+    use std::{collections::BTreeMap, time::Duration};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Protocol {
+        Tcp,
+        Udp,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    struct Rule {
+        id: ItemId,
+        chain: String,
+        protocol: Option<Protocol>,
+        dst_port: Vec<u16>,
+        disabled: bool,
+        comment: Option<String>,
+        log: Option<bool>,
+    }
+
+    fn attributes(attributes: &[(&str, &str)]) -> Vec<(String, Vec<u8>)> {
+        attributes
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_struct() {
+        let rule = Rule {
+            id: ItemId(0x1a),
+            chain: "forward".to_string(),
+            protocol: Some(Protocol::Tcp),
+            dst_port: vec![80, 443],
+            disabled: true,
+            comment: None,
+            log: Some(false),
+        };
+        let result = to_attributes(&rule).unwrap();
+        assert_eq!(
+            result,
+            attributes(&[
+                (".id", "*1A"),
+                ("chain", "forward"),
+                ("protocol", "tcp"),
+                ("dst-port", "80,443"),
+                ("disabled", "yes"),
+                ("log", "no"),
+            ])
+        );
+        let sentence: Vec<_> = result
+            .iter()
+            .map(|(key, value)| (key.as_bytes(), Some(value.as_slice())))
+            .collect();
+        assert_eq!(from_sentence::<Rule>(&sentence), Ok(rule));
+    }
+
+    #[test]
+    fn test_dotted_keys() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Entry {
+            id: ItemId,
+            #[serde(rename = ".nextid")]
+            next_id: Option<ItemId>,
+            #[serde(rename = ".dead")]
+            dead: Option<bool>,
+            #[serde(rename = ".about")]
+            about: Option<String>,
+            name: String,
+        }
+        let entry = Entry {
+            id: ItemId(1),
+            next_id: Some(ItemId(2)),
+            dead: Some(true),
+            about: Some("managed by dhcp".to_string()),
+            name: "ether1".to_string(),
+        };
+        let result = to_attributes(&entry).unwrap();
+        assert_eq!(
+            result,
+            attributes(&[
+                (".id", "*1"),
+                (".nextid", "*2"),
+                (".dead", "yes"),
+                (".about", "managed by dhcp"),
+                ("name", "ether1"),
+            ])
+        );
+        let sentence: Vec<_> = result
+            .iter()
+            .map(|(key, value)| (key.as_bytes(), Some(value.as_slice())))
+            .collect();
+        assert_eq!(from_sentence::<Entry>(&sentence), Ok(entry));
+    }
+
+    #[test]
+    fn test_values() {
+        #[derive(Serialize)]
+        struct Values {
+            timeout: RosDuration,
+            interval: Option<RosDuration>,
+            empty: (),
+            list: Vec<&'static str>,
+            name: &'static str,
+        }
+        let result = to_attributes(&Values {
+            timeout: RosDuration(Duration::from_secs(3 * 24 * 3600 + 30)),
+            interval: Some(RosDuration(Duration::from_millis(1500))),
+            empty: (),
+            list: vec![],
+            name: "straße",
+        })
+        .unwrap();
+        assert_eq!(
+            result,
+            vec![
+                ("timeout".to_string(), b"3d30s".to_vec()),
+                ("interval".to_string(), b"1s500ms".to_vec()),
+                ("empty".to_string(), Vec::new()),
+                ("list".to_string(), Vec::new()),
+                ("name".to_string(), b"stra\xdfe".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        let map = BTreeMap::from([("address", "10.0.0.1"), ("list", "blocked")]);
+        assert_eq!(
+            to_attributes(&map).unwrap(),
+            attributes(&[("address", "10.0.0.1"), ("list", "blocked")])
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        #[derive(Serialize)]
+        struct Outer {
+            inner: Inner,
+        }
+        #[derive(Serialize)]
+        struct Inner {
+            value: u8,
+        }
+        assert!(to_attributes(&Outer {
+            inner: Inner { value: 1 }
+        })
+        .is_err());
+        assert!(to_attributes(&42).is_err());
+        assert!(to_attributes(&["a", "b"]).is_err());
+
+        // neither std's nor a user defined `Duration` is picked up by its name
+        #[derive(Serialize)]
+        struct Duration {
+            secs: u64,
+            nanos: u32,
+        }
+        assert!(to_attributes(&BTreeMap::from([(
+            "timeout",
+            Duration { secs: 1, nanos: 0 }
+        )]))
+        .is_err());
+        assert!(to_attributes(&BTreeMap::from([(
+            "timeout",
+            std::time::Duration::from_secs(1)
+        )]))
+        .is_err());
+    }
+}
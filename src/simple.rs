@@ -1,4 +1,5 @@
 use crate::{
+    device::done_ret,
     error::Error,
     prelude::ParsedMessage,
     protocol::word::{TrapCategory, TrapResult},
@@ -14,6 +15,12 @@ pub enum SimpleResult {
         category: Option<TrapCategory>,
         message: Box<str>,
     },
+    /// The command has no results
+    Empty,
+    /// The command completed, `ret` holds its return value, e.g. the id of an added item
+    Done {
+        ret: Option<Box<str>>,
+    },
 }
 
 impl ParsedMessage for SimpleResult {
@@ -40,4 +47,14 @@ impl ParsedMessage for SimpleResult {
             message: Box::from(decode_latin1(message)),
         }
     }
+
+    fn process_empty(_: &Self::Context) -> Option<Self> {
+        Some(SimpleResult::Empty)
+    }
+
+    fn process_done(attributes: &[(&[u8], Option<&[u8]>)], _: &Self::Context) -> Option<Self> {
+        Some(SimpleResult::Done {
+            ret: done_ret(attributes),
+        })
+    }
 }
@@ -0,0 +1,96 @@
+use crate::error::Error;
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Formatter},
+    sync::{Arc, Mutex},
+};
+
+/// Hands out the tags of the commands on one connection.
+///
+/// A tag is only reused after the [`Tag`] holding it is dropped, even when the sequence wraps around.
+#[derive(Debug, Default)]
+pub(crate) struct TagAllocator {
+    state: Mutex<TagState>,
+}
+
+#[derive(Debug, Default)]
+struct TagState {
+    next: u16,
+    in_use: HashSet<u16>,
+}
+
+impl TagAllocator {
+    /// Reserves the next free tag, fails if all tags are in use
+    pub(crate) fn allocate(self: &Arc<Self>) -> Result<Tag, Error> {
+        let mut state = self.state.lock().expect("tag allocator poisoned");
+        if state.in_use.len() > u16::MAX as usize {
+            return Err(Error::TagsExhausted);
+        }
+        loop {
+            let tag = state.next;
+            state.next = state.next.wrapping_add(1);
+            if state.in_use.insert(tag) {
+                return Ok(Tag {
+                    value: tag,
+                    allocator: self.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// A reserved tag, released when dropped
+pub(crate) struct Tag {
+    value: u16,
+    allocator: Arc<TagAllocator>,
+}
+
+impl Tag {
+    pub(crate) fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl Debug for Tag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Tag").field(&self.value).finish()
+    }
+}
+
+impl Drop for Tag {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.allocator.state.lock() {
+            state.in_use.remove(&self.value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_tags_in_use() {
+        let allocator = Arc::new(TagAllocator::default());
+        let listen = allocator.allocate().unwrap();
+        assert_eq!(listen.value(), 0);
+        for expected in 1..=u16::MAX {
+            assert_eq!(allocator.allocate().unwrap().value(), expected);
+        }
+        // wrapped around, the tag of the running listen is skipped
+        assert_eq!(allocator.allocate().unwrap().value(), 1);
+        drop(listen);
+        assert_eq!(allocator.allocate().unwrap().value(), 2);
+    }
+
+    #[test]
+    fn test_all_tags_in_use() {
+        let allocator = Arc::new(TagAllocator::default());
+        let tags: Vec<_> = (0..=u16::MAX)
+            .map(|_| allocator.allocate().unwrap())
+            .collect();
+        assert!(matches!(allocator.allocate(), Err(Error::TagsExhausted)));
+        drop(tags);
+        assert!(allocator.allocate().is_ok());
+    }
+}
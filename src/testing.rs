@@ -0,0 +1,107 @@
+//! Helpers to simulate a RouterOS device in unit tests
+use crate::protocol::{command::CommandBuffer, word::read_length};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Encodes the given words as one terminated sentence
+pub(crate) fn encode_sentence(words: &[&str]) -> Vec<u8> {
+    let mut buffer = CommandBuffer::default();
+    for word in words {
+        buffer.write_word(word.as_bytes());
+    }
+    buffer.write_len(0);
+    buffer.0
+}
+
+/// Minimal device side of an API connection
+pub(crate) struct MockRouter<S> {
+    stream: S,
+    buffer: Vec<u8>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MockRouter<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Reads the next sentence sent by the client, `None` if the connection is closed
+    pub(crate) async fn read_sentence(&mut self) -> Option<Vec<String>> {
+        let mut sentence = Vec::new();
+        loop {
+            self.fill(1).await?;
+            let prefix_length = match self.buffer[0] {
+                c if c & 0x80 == 0x00 => 1,
+                c if c & 0xC0 == 0x80 => 2,
+                c if c & 0xE0 == 0xC0 => 3,
+                c if c & 0xF0 == 0xE0 => 4,
+                _ => 5,
+            };
+            self.fill(prefix_length).await?;
+            let (length, inc) = read_length(&self.buffer).expect("Invalid length prefix");
+            self.fill(inc + length as usize).await?;
+            let word: Vec<u8> = self
+                .buffer
+                .drain(..inc + length as usize)
+                .skip(inc)
+                .collect();
+            if word.is_empty() {
+                return Some(sentence);
+            }
+            sentence.push(String::from_utf8(word).expect("Non utf8 word"));
+        }
+    }
+
+    /// Sends one sentence to the client
+    pub(crate) async fn reply(&mut self, words: &[&str]) {
+        self.stream
+            .write_all(&encode_sentence(words))
+            .await
+            .expect("Cannot write reply");
+    }
+
+    /// Sends a recorded sentence as `!re` reply to the command with the given tag
+    pub(crate) async fn reply_recorded(&mut self, tag: u16, sentence: &[(&[u8], Option<&[u8]>)]) {
+        let tag = format!(".tag={tag}");
+        let attributes: Vec<_> = sentence
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "={}={}",
+                    std::str::from_utf8(key).expect("Non utf8 key"),
+                    std::str::from_utf8(value.unwrap_or_default()).expect("Non utf8 value")
+                )
+            })
+            .collect();
+        let mut words = vec!["!re", tag.as_str()];
+        words.extend(attributes.iter().map(String::as_str));
+        self.reply(&words).await;
+    }
+
+    /// Waits for a `/login` and accepts it
+    pub(crate) async fn accept_login(&mut self) -> Vec<String> {
+        let login = self.read_sentence().await.expect("No login received");
+        assert_eq!(login[0], "/login");
+        let tag = format!(".tag={}", tag_of(&login));
+        self.reply(&["!done", &tag]).await;
+        login
+    }
+
+    async fn fill(&mut self, count: usize) -> Option<()> {
+        while self.buffer.len() < count {
+            if self.stream.read_buf(&mut self.buffer).await.ok()? == 0 {
+                return None;
+            }
+        }
+        Some(())
+    }
+}
+
+/// Extracts the value of the `.tag` word of a sentence
+pub(crate) fn tag_of(sentence: &[String]) -> &str {
+    sentence
+        .iter()
+        .find_map(|w| w.strip_prefix(".tag="))
+        .expect("Sentence without tag")
+}
@@ -0,0 +1,363 @@
+//! Connect to the `api-ssl` service (default port 8729) over TLS
+use crate::{
+    error::Error,
+    prelude::{ConnectOptions, MikrotikDevice, ParsedMessage},
+    protocol::WordSequenceItem,
+};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, ServerName, UnixTime},
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::TlsConnector;
+
+pub use rustls::pki_types::CertificateDer;
+
+/// Defines how the certificate presented by the device is verified
+#[derive(Debug, Clone)]
+pub enum TlsVerification {
+    /// Verify the certificate chain and the server name against the given root certificates
+    CaBundle(Vec<CertificateDer<'static>>),
+    /// Accept only a device certificate with the given SHA-256 fingerprint
+    Fingerprint([u8; 32]),
+    /// Accept every certificate, e.g. the self-signed one of a factory configured router
+    AcceptSelfSigned,
+}
+
+impl TlsVerification {
+    /// Builds a [`TlsVerification::CaBundle`] from PEM encoded certificates
+    pub fn ca_bundle_pem(pem: &[u8]) -> Result<Self, Error> {
+        let certificates = CertificateDer::pem_slice_iter(pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Tls(rustls::Error::General(e.to_string())))?;
+        Ok(TlsVerification::CaBundle(certificates))
+    }
+
+    fn client_config(self) -> Result<ClientConfig, Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let config = match self {
+            TlsVerification::CaBundle(certificates) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in certificates {
+                    roots.add(certificate)?;
+                }
+                builder.with_root_certificates(roots)
+            }
+            TlsVerification::Fingerprint(fingerprint) => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+                    fingerprint: Some(fingerprint),
+                    provider,
+                })),
+            TlsVerification::AcceptSelfSigned => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+                    fingerprint: None,
+                    provider,
+                })),
+        };
+        Ok(config.with_no_client_auth())
+    }
+}
+
+/// tokio-rustls reports a failed handshake, e.g. a rejected certificate, as io error
+/// wrapping the rustls error, which is unwrapped into [`Error::Tls`]
+fn handshake_error(error: std::io::Error) -> Error {
+    match error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+    {
+        Some(tls_error) => Error::Tls(tls_error.clone()),
+        None => Error::Io(Arc::new(error)),
+    }
+}
+
+impl<D: ParsedMessage> MikrotikDevice<D> {
+    /// Connect to the `api-ssl` service of a device and login, using the default [`ConnectOptions`]
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the device, usually on port 8729
+    /// * `server_name` - DNS name or ip address expected in the certificate of the device
+    /// * `verification` - how the certificate of the device is verified
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect_tls<
+        'u,
+        'p,
+        A: ToSocketAddrs,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        addr: A,
+        server_name: &str,
+        verification: TlsVerification,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        ConnectOptions::default()
+            .connect_tls(addr, server_name, verification, username, password)
+            .await
+    }
+}
+
+impl ConnectOptions {
+    /// Connect to the `api-ssl` service of a device and login.
+    /// The connect timeout covers the tcp connection and the TLS handshake.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the device, usually on port 8729
+    /// * `server_name` - DNS name or ip address expected in the certificate of the device
+    /// * `verification` - how the certificate of the device is verified
+    /// * `username` - login name
+    /// * `password` - optional password
+    pub async fn connect_tls<
+        'u,
+        'p,
+        D: ParsedMessage,
+        A: ToSocketAddrs,
+        U: Into<WordSequenceItem<'u>>,
+        P: Into<WordSequenceItem<'p>>,
+    >(
+        &self,
+        addr: A,
+        server_name: &str,
+        verification: TlsVerification,
+        username: U,
+        password: Option<P>,
+    ) -> Result<MikrotikDevice<D>, Error> {
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|_| Error::InvalidServerName(Box::from(server_name)))?;
+        let connector = TlsConnector::from(Arc::new(verification.client_config()?));
+
+        let (name, stream) = self
+            .with_connect_timeout(async {
+                let stream = TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                let name = stream.peer_addr()?.to_string();
+                Ok((name, connector.connect(server_name, stream).await))
+            })
+            .await?;
+        let stream = stream.map_err(handshake_error)?;
+
+        self.connect_stream(&name, stream, username, password).await
+    }
+}
+
+/// Skips the chain validation, optionally checks the fingerprint of the device certificate
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    fingerprint: Option<[u8; 32]>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self.fingerprint {
+            Some(expected) if Sha256::digest(end_entity).as_slice() != expected => Err(
+                rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure),
+            ),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simple::SimpleResult, testing::MockRouter};
+    use rustls::{pki_types::PrivateKeyDer, ServerConfig};
+    use std::net::SocketAddr;
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+    use tokio_stream::StreamExt;
+
+    struct TestServer {
+        addr: SocketAddr,
+        certificate: CertificateDer<'static>,
+    }
+
+    /// Starts a TLS stand-in for a device accepting one login and answering one command
+    async fn start_server() -> TestServer {
+        let key = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let certificate = key.cert.der().clone();
+        let private_key = PrivateKeyDer::try_from(key.key_pair.serialize_der()).unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![certificate.clone()], private_key)
+                .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut router = MockRouter::new(stream);
+                    router.accept_login().await;
+                    while let Some(command) = router.read_sentence().await {
+                        let tag = format!(".tag={}", crate::testing::tag_of(&command));
+                        router.reply(&["!re", &tag, "=name=router"]).await;
+                        router.reply(&["!done", &tag]).await;
+                    }
+                });
+            }
+        });
+        TestServer { addr, certificate }
+    }
+
+    async fn identity(device: MikrotikDevice<SimpleResult>) -> Option<Box<str>> {
+        let mut stream = device
+            .send_simple_command(b"/system/identity/print", ())
+            .await
+            .unwrap();
+        match stream.next().await {
+            Some(SimpleResult::Sentence(attributes)) => attributes.get("name").cloned().flatten(),
+            other => panic!("Unexpected response: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_accept_self_signed() {
+        let server = start_server().await;
+        let device = MikrotikDevice::<SimpleResult>::connect_tls(
+            server.addr,
+            "127.0.0.1",
+            TlsVerification::AcceptSelfSigned,
+            b"admin",
+            Some(b"secret"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(identity(device).await.as_deref(), Some("router"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_ca_bundle() {
+        let server = start_server().await;
+        let device = MikrotikDevice::<SimpleResult>::connect_tls(
+            server.addr,
+            "localhost",
+            TlsVerification::CaBundle(vec![server.certificate.clone()]),
+            b"admin",
+            Some(b"secret"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(identity(device).await.as_deref(), Some("router"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_ca_bundle_wrong_name() {
+        let server = start_server().await;
+        let result = MikrotikDevice::<SimpleResult>::connect_tls(
+            server.addr,
+            "router.example.com",
+            TlsVerification::CaBundle(vec![server.certificate.clone()]),
+            b"admin",
+            Some(b"secret"),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::Tls(rustls::Error::InvalidCertificate(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connect_fingerprint() {
+        let server = start_server().await;
+        let fingerprint = Sha256::digest(&server.certificate).into();
+        let device = MikrotikDevice::<SimpleResult>::connect_tls(
+            server.addr,
+            "127.0.0.1",
+            TlsVerification::Fingerprint(fingerprint),
+            b"admin",
+            Some(b"secret"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(identity(device).await.as_deref(), Some("router"));
+
+        let result = MikrotikDevice::<SimpleResult>::connect_tls(
+            server.addr,
+            "127.0.0.1",
+            TlsVerification::Fingerprint([0; 32]),
+            b"admin",
+            Some(b"secret"),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::Tls(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_ca_bundle_pem() {
+        let key = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let pem = key.cert.pem();
+        match TlsVerification::ca_bundle_pem(pem.as_bytes()).unwrap() {
+            TlsVerification::CaBundle(certificates) => {
+                assert_eq!(certificates, vec![key.cert.der().clone()])
+            }
+            other => panic!("Unexpected verification: {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    de::{from_sentence, DeserializeError},
+    device::done_ret,
+    error::Error,
+    prelude::ParsedMessage,
+    protocol::word::{TrapCategory, TrapResult},
+};
+use encoding_rs::mem::decode_latin1;
+use serde::de::DeserializeOwned;
+use std::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+};
+
+/// Replies deserialized into `T` by [`from_sentence`], the other events as in [`SimpleResult`](crate::simple::SimpleResult)
+///
+/// # Examples
+/// ```no_run
+/// # async fn example() -> Result<(), mikrotik_api::error::Error> {
+/// use mikrotik_api::{prelude::*, typed::Typed};
+/// use serde::Deserialize;
+/// use tokio_stream::StreamExt;
+///
+/// #[derive(Debug, Clone, Deserialize)]
+/// struct Identity {
+///     name: String,
+/// }
+///
+/// let device: MikrotikDevice<Typed<Identity>> =
+///     MikrotikDevice::connect(("192.168.88.1", 8728), b"admin", Some(b"password")).await?;
+/// let mut stream = device
+///     .send_simple_command(b"/system/identity/print", Default::default())
+///     .await?;
+/// while let Some(Typed::Item(identity)) = stream.next().await {
+///     println!("{}", identity.name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub enum Typed<T> {
+    Item(T),
+    /// A reply could not be deserialized
+    Invalid(DeserializeError),
+    Error(Error),
+    Trap {
+        category: Option<TrapCategory>,
+        message: Box<str>,
+    },
+    /// The command has no results
+    Empty,
+    /// The command completed, `ret` holds its return value, e.g. the id of an added item
+    Done {
+        ret: Option<Box<str>>,
+    },
+}
+
+/// Context of a [`Typed`] command, only carries the type
+pub struct TypedContext<T>(PhantomData<fn() -> T>);
+
+impl<T> Debug for TypedContext<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TypedContext")
+    }
+}
+
+impl<T> Default for TypedContext<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Clone for TypedContext<T> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> ParsedMessage for Typed<T> {
+    type Context = TypedContext<T>;
+
+    fn parse_message(sentence: &[(&[u8], Option<&[u8]>)], _: &Self::Context) -> Self {
+        match from_sentence(sentence) {
+            Ok(item) => Typed::Item(item),
+            Err(e) => Typed::Invalid(e),
+        }
+    }
+
+    fn process_error(error: &Error, _: &Self::Context) -> Self {
+        Typed::Error(error.clone())
+    }
+
+    fn process_trap(TrapResult { category, message }: TrapResult, _: &Self::Context) -> Self {
+        Typed::Trap {
+            category,
+            message: Box::from(decode_latin1(message)),
+        }
+    }
+
+    fn process_empty(_: &Self::Context) -> Option<Self> {
+        Some(Typed::Empty)
+    }
+
+    fn process_done(attributes: &[(&[u8], Option<&[u8]>)], _: &Self::Context) -> Option<Self> {
+        Some(Typed::Done {
+            ret: done_ret(attributes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::MikrotikDevice, testing::MockRouter};
+    use serde::Deserialize;
+    use tokio::io::duplex;
+    use tokio_stream::StreamExt;
+
+    #[derive(Debug, Clone, Deserialize, PartialEq)]
+    struct Address {
+        address: String,
+        interface: String,
+        disabled: bool,
+    }
+
+    #[tokio::test]
+    async fn test_typed_replies() {
+        let (client, server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut router = MockRouter::new(server);
+            router.accept_login().await;
+            router.read_sentence().await.unwrap();
+            router
+                .reply(&[
+                    "!re",
+                    ".tag=1",
+                    "=.id=*1",
+                    "=address=192.168.88.1/24",
+                    "=interface=bridge",
+                    "=disabled=false",
+                ])
+                .await;
+            router
+                .reply(&["!re", ".tag=1", "=address=10.0.0.1/24"])
+                .await;
+            router.reply(&["!done", ".tag=1"]).await;
+        });
+        let device =
+            MikrotikDevice::<Typed<Address>>::connect_stream("test", client, b"admin", Some(b""))
+                .await
+                .unwrap();
+        let replies: Vec<_> = device
+            .send_simple_command(b"/ip/address/print", Default::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        match replies.as_slice() {
+            [Typed::Item(address), Typed::Invalid(_), Typed::Done { ret: None }] => {
+                assert_eq!(
+                    address,
+                    &Address {
+                        address: "192.168.88.1/24".to_string(),
+                        interface: "bridge".to_string(),
+                        disabled: false,
+                    }
+                )
+            }
+            other => panic!("Unexpected replies: {other:?}"),
+        }
+    }
+}
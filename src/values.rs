@@ -0,0 +1,954 @@
+//! Typed representations of the values RouterOS uses in attributes
+/// Address with prefix length as used by `/ip/address`, `/ip/route` or address lists, e.g.
+/// `192.168.88.1/24`. The host bits are kept, a plain address is read as host prefix (`/32` or `/128`).
+pub use ipnet::IpNet;
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    time::Duration,
+};
+use thiserror::Error;
+
+/// A value of an attribute could not be parsed
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Invalid {kind}: {value:?}")]
+pub struct ParseValueError {
+    pub kind: &'static str,
+    pub value: Box<str>,
+}
+
+impl ParseValueError {
+    pub(crate) fn new(kind: &'static str, value: &str) -> Self {
+        Self {
+            kind,
+            value: Box::from(value),
+        }
+    }
+}
+
+/// Conversion of an attribute value of a reply, used by [`RouterOsResource`](crate::resource::RouterOsResource)
+pub trait FromAttribute: Sized {
+    /// Parses the text of the attribute
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError>;
+    /// Value if the attribute is missing in the reply, `None` if the attribute is required
+    fn missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Conversion into an attribute value of a command, see [`CommandBuilder::value_attribute`](crate::prelude::CommandBuilder::value_attribute)
+pub trait ToAttribute {
+    /// Text of the attribute, `None` to omit the attribute
+    fn to_attribute(&self) -> Option<Cow<'_, str>>;
+}
+
+impl<T: ToAttribute + ?Sized> ToAttribute for &T {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        (**self).to_attribute()
+    }
+}
+
+impl<T: FromAttribute> FromAttribute for Option<T> {
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+        match T::from_attribute(value) {
+            Ok(value) => Ok(Some(value)),
+            // RouterOS prints unset values as empty attribute
+            Err(_) if value.is_empty() => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: ToAttribute> ToAttribute for Option<T> {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(T::to_attribute)
+    }
+}
+
+impl FromAttribute for String {
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+        Ok(value.to_string())
+    }
+}
+
+impl FromAttribute for Box<str> {
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+        Ok(Box::from(value))
+    }
+}
+
+impl ToAttribute for str {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl ToAttribute for String {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl ToAttribute for Box<str> {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+/// Accepts `yes`/`no` as written by RouterOS and `true`/`false` as printed by some menus
+impl FromAttribute for bool {
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+        match value {
+            "yes" | "true" => Ok(true),
+            "no" | "false" => Ok(false),
+            _ => Err(ParseValueError::new("bool", value)),
+        }
+    }
+}
+
+impl ToAttribute for bool {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(if *self { "yes" } else { "no" }))
+    }
+}
+
+/// Implements the attribute conversions of std types by their [`FromStr`] and [`Display`]
+macro_rules! std_attribute {
+    ($($ty:ty),*) => {
+        $(
+            impl FromAttribute for $ty {
+                fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+                    value
+                        .parse()
+                        .map_err(|_| ParseValueError::new(stringify!($ty), value))
+                }
+            }
+
+            impl ToAttribute for $ty {
+                fn to_attribute(&self) -> Option<Cow<'_, str>> {
+                    Some(Cow::Owned(self.to_string()))
+                }
+            }
+        )*
+    };
+}
+
+std_attribute!(u8, u16, u32, u64, usize, i8, i16, i32, i64, f32, f64);
+std_attribute!(IpAddr, Ipv4Addr, Ipv6Addr);
+
+/// Implements the attribute and serde conversions of a type by its [`FromStr`] and [`Display`]
+macro_rules! text_value {
+    ($ty:ty) => {
+        impl $crate::values::FromAttribute for $ty {
+            fn from_attribute(value: &str) -> Result<Self, $crate::values::ParseValueError> {
+                value.parse()
+            }
+        }
+
+        impl $crate::values::ToAttribute for $ty {
+            fn to_attribute(&self) -> Option<std::borrow::Cow<'_, str>> {
+                Some(std::borrow::Cow::Owned(self.to_string()))
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let text = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+                text.parse().map_err(serde::de::Error::custom)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+    };
+}
+pub(crate) use text_value;
+
+/// Declares an enum of the names a RouterOS attribute takes and implements its conversions.
+///
+/// Every variant before the `;` is written as the given name, the variant after it keeps any
+/// other name, so names of newer RouterOS versions are not rejected:
+///
+/// ```ignore
+/// named_enum! {
+///     #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///     pub enum Chain {
+///         Input = "input",
+///         Forward = "forward";
+///         Custom(Box<str>),
+///     }
+/// }
+/// ```
+macro_rules! named_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $text:literal),+;
+            $(#[$other_meta:meta])*
+            $other:ident(Box<str>) $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant,)+
+            $(#[$other_meta])*
+            $other(Box<str>),
+        }
+
+        impl $name {
+            #[cfg(test)]
+            const NAMES: &'static [(&'static str, $name)] = &[$(($text, $name::$variant)),+];
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = $crate::values::ParseValueError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(match s {
+                    $($text => $name::$variant,)+
+                    _ => $name::$other(Box::from(s)),
+                })
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(match self {
+                    $($name::$variant => $text,)+
+                    $name::$other(name) => name,
+                })
+            }
+        }
+
+        $crate::values::text_value!($name);
+    };
+}
+pub(crate) use named_enum;
+
+/// Internal id of an item in a menu, written as `*` followed by a hexadecimal number, e.g. `*1A`.
+///
+/// It is found in the `.id` attribute of printed items and is returned by `add` commands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ItemId(pub u32);
+
+/// Name of [`ItemId`] in line with the other RouterOS value types
+pub type RosId = ItemId;
+
+impl FromStr for ItemId {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('*')
+            // `from_str_radix` would accept a leading sign
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(ItemId)
+            .ok_or_else(|| ParseValueError::new("item id", s))
+    }
+}
+
+impl Display for ItemId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "*{:X}", self.0)
+    }
+}
+
+text_value!(ItemId);
+
+/// Boolean written as `yes`/`no`, `true`/`false` is accepted as well
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RosBool(pub bool);
+
+impl FromStr for RosBool {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        bool::from_attribute(s).map(RosBool)
+    }
+}
+
+impl Display for RosBool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0 { "yes" } else { "no" })
+    }
+}
+
+impl From<bool> for RosBool {
+    fn from(value: bool) -> Self {
+        RosBool(value)
+    }
+}
+
+impl From<RosBool> for bool {
+    fn from(value: RosBool) -> Self {
+        value.0
+    }
+}
+
+text_value!(RosBool);
+
+/// Time span in the notation of RouterOS.
+///
+/// Parses `1w2d3h4m5s`, `250ms` as well as the clock form `1w2d03:04:05` of RouterOS 6 and
+/// is written like `1w2d3h4m5s`. Fractions of a millisecond are dropped when written.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RosDuration(pub Duration);
+
+impl RosDuration {
+    /// Length of the units in nanoseconds, `ms` is matched before `m`
+    const UNITS: [(&'static str, u128); 8] = [
+        ("w", 7 * 24 * 3600 * 1_000_000_000),
+        ("d", 24 * 3600 * 1_000_000_000),
+        ("h", 3600 * 1_000_000_000),
+        ("ms", 1_000_000),
+        ("m", 60 * 1_000_000_000),
+        ("s", 1_000_000_000),
+        ("us", 1_000),
+        ("ns", 1),
+    ];
+}
+
+/// Converts a decimal number like `12` or `1.5` counting `unit`s
+fn parse_decimal(number: &str, unit: u128) -> Option<u128> {
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if integer.is_empty() || !integer.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) || fraction.len() > 9 {
+        return None;
+    }
+    let integer: u128 = integer.parse().ok()?;
+    let fraction_value: u128 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse().ok()?
+    };
+    integer
+        .checked_mul(unit)?
+        .checked_add(fraction_value * unit / 10u128.pow(fraction.len() as u32))
+}
+
+impl FromStr for RosDuration {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseValueError::new("duration", s);
+        let mut rest = s;
+        let mut nanos: u128 = 0;
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        while !rest.is_empty() {
+            let number_length = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let (number, tail) = rest.split_at(number_length);
+            if tail.starts_with(':') {
+                // Clock form hh:mm:ss, always at the end
+                let mut parts = rest.split(':');
+                let (Some(hours), Some(minutes), Some(seconds), None) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(invalid());
+                };
+                nanos = parse_decimal(hours, 3600 * 1_000_000_000)
+                    .zip(parse_decimal(minutes, 60 * 1_000_000_000))
+                    .zip(parse_decimal(seconds, 1_000_000_000))
+                    .and_then(|((hours, minutes), seconds)| {
+                        hours.checked_add(minutes)?.checked_add(seconds)
+                    })
+                    .and_then(|clock| nanos.checked_add(clock))
+                    .ok_or_else(invalid)?;
+                break;
+            }
+            let (unit, length) = Self::UNITS
+                .iter()
+                .find(|(unit, _)| tail.starts_with(unit))
+                // A plain number counts seconds
+                .map_or(("", 1_000_000_000), |(unit, length)| (*unit, *length));
+            if unit.is_empty() && !tail.is_empty() {
+                return Err(invalid());
+            }
+            nanos = parse_decimal(number, length)
+                .and_then(|value| nanos.checked_add(value))
+                .ok_or_else(invalid)?;
+            rest = &tail[unit.len()..];
+        }
+        let seconds = u64::try_from(nanos / 1_000_000_000).map_err(|_| invalid())?;
+        Ok(RosDuration(Duration::new(
+            seconds,
+            (nanos % 1_000_000_000) as u32,
+        )))
+    }
+}
+
+impl Display for RosDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format_duration(self.0))
+    }
+}
+
+impl From<Duration> for RosDuration {
+    fn from(value: Duration) -> Self {
+        RosDuration(value)
+    }
+}
+
+impl From<RosDuration> for Duration {
+    fn from(value: RosDuration) -> Self {
+        value.0
+    }
+}
+
+text_value!(RosDuration);
+
+/// Writes a duration in the notation of RouterOS, e.g. `1w2d3h4m5s` or `250ms`.
+/// Fractions of a millisecond are dropped.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    const UNITS: [(&str, u64); 5] = [
+        ("w", 7 * 24 * 3600),
+        ("d", 24 * 3600),
+        ("h", 3600),
+        ("m", 60),
+        ("s", 1),
+    ];
+    let mut result = String::new();
+    let mut seconds = duration.as_secs();
+    for (unit, length) in UNITS {
+        if seconds >= length {
+            result.push_str(&format!("{}{unit}", seconds / length));
+            seconds %= length;
+        }
+    }
+    let millis = duration.subsec_millis();
+    if millis > 0 {
+        result.push_str(&format!("{millis}ms"));
+    }
+    if result.is_empty() {
+        result.push_str("0s");
+    }
+    result
+}
+
+/// Amount of bytes or bits, e.g. `1500`, `10M` or `1.5GiB`.
+///
+/// The suffixes `k`, `M`, `G` and `T` count in powers of 1000, `KiB`, `MiB`, `GiB` and `TiB`
+/// in powers of 1024. It is written as plain number.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RosBytes(pub u64);
+
+impl RosBytes {
+    const UNITS: [(&'static str, u128); 9] = [
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+        ("k", 1_000),
+        ("K", 1_000),
+        ("M", 1_000_000),
+        ("G", 1_000_000_000),
+        ("T", 1_000_000_000_000),
+    ];
+}
+
+impl FromStr for RosBytes {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number_length = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(number_length);
+        let factor = match unit {
+            "" => Some(1),
+            unit => Self::UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, factor)| *factor),
+        };
+        factor
+            .and_then(|factor| parse_decimal(number, factor))
+            .and_then(|bytes| u64::try_from(bytes).ok())
+            .map(RosBytes)
+            .ok_or_else(|| ParseValueError::new("byte count", s))
+    }
+}
+
+impl Display for RosBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+text_value!(RosBytes);
+
+/// Comma separated list of values, e.g. `ether1,ether2`. An empty text is an empty list.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RosList<T>(pub Vec<T>);
+
+impl<T> Default for RosList<T> {
+    fn default() -> Self {
+        RosList(Vec::new())
+    }
+}
+
+impl<T: FromAttribute> FromStr for RosList<T> {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_attribute(s)
+    }
+}
+
+impl<T: Display> Display for RosList<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, item) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            Display::fmt(item, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> From<Vec<T>> for RosList<T> {
+    fn from(value: Vec<T>) -> Self {
+        RosList(value)
+    }
+}
+
+impl<T: FromAttribute> FromAttribute for RosList<T> {
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+        if value.is_empty() {
+            return Ok(RosList(Vec::new()));
+        }
+        value
+            .split(',')
+            .map(T::from_attribute)
+            .collect::<Result<_, _>>()
+            .map(RosList)
+    }
+}
+
+impl<T: ToAttribute> ToAttribute for RosList<T> {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        let items: Vec<_> = self.0.iter().filter_map(T::to_attribute).collect();
+        Some(Cow::Owned(items.join(",")))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: FromAttribute> serde::Deserialize<'de> for RosList<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <Cow<'de, str>>::deserialize(deserializer)?;
+        Self::from_attribute(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: ToAttribute> serde::Serialize for RosList<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_attribute().unwrap_or_default())
+    }
+}
+
+/// Hardware address of an interface, written as `AA:BB:CC:DD:EE:FF`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl FromStr for MacAddress {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut address = [0; 6];
+        let mut parts = s.split([':', '-']);
+        for byte in address.iter_mut() {
+            *byte = parts
+                .next()
+                .filter(|part| part.len() == 2)
+                .and_then(|part| u8::from_str_radix(part, 16).ok())
+                .ok_or_else(|| ParseValueError::new("mac address", s))?;
+        }
+        if parts.next().is_some() {
+            return Err(ParseValueError::new("mac address", s));
+        }
+        Ok(MacAddress(address))
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02X}:{b:02X}:{c:02X}:{d:02X}:{e:02X}:{g:02X}")
+    }
+}
+
+text_value!(MacAddress);
+
+/// Former address with prefix length, replaced by the re-exported [`IpNet`].
+///
+/// The fields `address` and `prefix_len` became the methods [`IpNet::addr`] and
+/// [`IpNet::prefix_len`], `IpPrefix::new` is [`IpNet::new`] returning a `Result`
+/// and `IpPrefix::network` is [`IpNet::trunc`].
+#[deprecated(note = "use `IpNet`")]
+pub type IpPrefix = IpNet;
+
+impl FromAttribute for IpNet {
+    fn from_attribute(value: &str) -> Result<Self, ParseValueError> {
+        value
+            .parse()
+            .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+            .map_err(|_| ParseValueError::new("ip prefix", value))
+    }
+}
+
+impl ToAttribute for IpNet {
+    fn to_attribute(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Owned(self.to_string()))
+    }
+}
+
+/// Local time of the device.
+///
+/// Parses the form `jan/02/2024 10:00:00` of RouterOS 6 and early 7 releases and the
+/// ISO form `2024-01-02 10:00:00` of recent releases, the time is optional. It is written in ISO form.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RosDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl RosDateTime {
+    const MONTHS: [&'static str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    /// Number of days of a month, 1 to 12, in the gregorian calendar
+    fn days_in_month(year: u16, month: u8) -> u8 {
+        match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+}
+
+/// Splits a text into exactly three parts
+fn split3(text: &str, separator: char) -> Option<[&str; 3]> {
+    let mut parts = text.split(separator);
+    let result = [parts.next()?, parts.next()?, parts.next()?];
+    parts.next().is_none().then_some(result)
+}
+
+impl FromStr for RosDateTime {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseValueError::new("date", s);
+        let (date, time) = s.split_once(' ').unwrap_or((s, "00:00:00"));
+        let [hour, minute, second] = split3(time, ':').ok_or_else(invalid)?;
+        let (year, month, day) = if date.contains('/') {
+            let [month, day, year] = split3(date, '/').ok_or_else(invalid)?;
+            let month = Self::MONTHS
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(month))
+                .ok_or_else(invalid)?;
+            (year, (month + 1).to_string(), day)
+        } else {
+            let [year, month, day] = split3(date, '-').ok_or_else(invalid)?;
+            (year, month.to_string(), day)
+        };
+        let number = |text: &str, max: u8| {
+            text.parse::<u8>()
+                .ok()
+                .filter(|value| *value <= max)
+                .ok_or_else(invalid)
+        };
+        let date_time = RosDateTime {
+            year: year.parse().map_err(|_| invalid())?,
+            month: number(&month, 12)?,
+            day: number(day, 31)?,
+            hour: number(hour, 23)?,
+            minute: number(minute, 59)?,
+            second: number(second, 59)?,
+        };
+        if date_time.month == 0
+            || date_time.day == 0
+            || date_time.day > Self::days_in_month(date_time.year, date_time.month)
+        {
+            return Err(invalid());
+        }
+        Ok(date_time)
+    }
+}
+
+impl Display for RosDateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+text_value!(RosDateTime);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_id() {
+        assert_eq!("*1A".parse(), Ok(ItemId(0x1a)));
+        assert_eq!("*ffffffff".parse(), Ok(ItemId(u32::MAX)));
+        assert_eq!(ItemId(0x1a).to_string(), "*1A");
+        assert!("1A".parse::<ItemId>().is_err());
+        assert!("*".parse::<ItemId>().is_err());
+        assert!("*xyz".parse::<ItemId>().is_err());
+        assert!("*+1A".parse::<ItemId>().is_err());
+        assert!("*-1".parse::<ItemId>().is_err());
+    }
+
+    #[test]
+    fn test_attributes() {
+        assert_eq!(bool::from_attribute("yes"), Ok(true));
+        assert_eq!(bool::from_attribute("false"), Ok(false));
+        assert!(bool::from_attribute("maybe").is_err());
+        assert_eq!(true.to_attribute().as_deref(), Some("yes"));
+        assert_eq!(u16::from_attribute("1500"), Ok(1500));
+        assert!(u16::from_attribute("-1").is_err());
+        assert_eq!(Option::<u16>::from_attribute("1"), Ok(Some(1)));
+        assert_eq!(Option::<u16>::missing(), Some(None));
+        assert_eq!(u16::missing(), None);
+        assert_eq!(None::<u16>.to_attribute(), None);
+        assert_eq!(Some(ItemId(2)).to_attribute().as_deref(), Some("*2"));
+        assert_eq!(
+            IpAddr::from_attribute("fe80::1"),
+            Ok(IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)))
+        );
+        assert!(Ipv4Addr::from_attribute("fe80::1").is_err());
+    }
+
+    #[test]
+    fn test_duration() {
+        let parse = |s: &str| s.parse::<RosDuration>().map(|d| d.0);
+        assert_eq!(parse("0s"), Ok(Duration::ZERO));
+        assert_eq!(parse("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse("1m30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(
+            parse("1w2d03:04:05"),
+            Ok(Duration::from_secs(9 * 24 * 3600 + 3 * 3600 + 4 * 60 + 5))
+        );
+        assert_eq!(parse("00:00:10.5"), Ok(Duration::from_millis(10_500)));
+        assert_eq!(parse("1h2m3s4ms5us"), Ok(Duration::new(3723, 4_005_000)));
+        for invalid in [
+            "",
+            "1x",
+            "s",
+            "1:2",
+            "1:2:3:4",
+            "1.2.3s",
+            "99999999999999999999999999:00:00",
+            "1w99999999999999999999999999:99999999999999999999999999:00",
+        ] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+
+        assert_eq!(RosDuration(Duration::ZERO).to_string(), "0s");
+        assert_eq!(RosDuration(Duration::from_millis(250)).to_string(), "250ms");
+        assert_eq!(
+            RosDuration(Duration::from_secs(9 * 24 * 3600 + 3 * 3600 + 5)).to_string(),
+            "1w2d3h5s"
+        );
+        assert_eq!(
+            RosDuration(Duration::from_millis(61_500)).to_string(),
+            "1m1s500ms"
+        );
+        for text in ["1w2d3h4m5s", "500ms", "1d", "0s"] {
+            assert_eq!(
+                parse(text).map(|d| RosDuration(d).to_string()).as_deref(),
+                Ok(text)
+            );
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!("1500".parse(), Ok(RosBytes(1500)));
+        assert_eq!("10M".parse(), Ok(RosBytes(10_000_000)));
+        assert_eq!("64k".parse(), Ok(RosBytes(64_000)));
+        assert_eq!("1.5GiB".parse(), Ok(RosBytes(3 << 29)));
+        assert_eq!("512KiB".parse(), Ok(RosBytes(512 * 1024)));
+        assert!("10X".parse::<RosBytes>().is_err());
+        assert!("M".parse::<RosBytes>().is_err());
+        assert_eq!(RosBytes(10_000_000).to_string(), "10000000");
+    }
+
+    #[test]
+    fn test_bool_and_list() {
+        assert_eq!("yes".parse(), Ok(RosBool(true)));
+        assert_eq!("false".parse(), Ok(RosBool(false)));
+        assert_eq!(RosBool(false).to_string(), "no");
+
+        let list: RosList<String> = RosList::from_attribute("ether1,ether2").unwrap();
+        assert_eq!(list.0, ["ether1", "ether2"]);
+        assert_eq!(list.to_attribute().as_deref(), Some("ether1,ether2"));
+        assert_eq!(
+            RosList::<String>::from_attribute(""),
+            Ok(RosList::default())
+        );
+        let ids: RosList<ItemId> = "*1,*A".parse().unwrap();
+        assert_eq!(ids.0, [ItemId(1), ItemId(10)]);
+        assert_eq!(ids.to_string(), "*1,*A");
+        assert!("*1,x".parse::<RosList<ItemId>>().is_err());
+        let names: RosList<String> = "a,b".parse().unwrap();
+        assert_eq!(names.0, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_mac_address() {
+        let mac: MacAddress = "4c:5e:0c:01:02:0A".parse().unwrap();
+        assert_eq!(mac, MacAddress([0x4c, 0x5e, 0x0c, 0x01, 0x02, 0x0a]));
+        assert_eq!(mac.to_string(), "4C:5E:0C:01:02:0A");
+        assert_eq!("4C-5E-0C-01-02-0A".parse(), Ok(mac));
+        for invalid in [
+            "4C:5E:0C:01:02",
+            "4C:5E:0C:01:02:0A:00",
+            "4C:5E:0C:01:02:0",
+            "xx:5E:0C:01:02:0A",
+        ] {
+            assert!(invalid.parse::<MacAddress>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_ip_prefix() {
+        let prefix = IpNet::from_attribute("192.168.88.1/24").unwrap();
+        assert_eq!(prefix.addr(), IpAddr::V4(Ipv4Addr::new(192, 168, 88, 1)));
+        assert_eq!(prefix.prefix_len(), 24);
+        assert_eq!(prefix.to_attribute().as_deref(), Some("192.168.88.1/24"));
+        assert_eq!(prefix.trunc().to_string(), "192.168.88.0/24");
+        assert_eq!(
+            IpNet::from_attribute("10.0.0.1")
+                .unwrap()
+                .to_attribute()
+                .as_deref(),
+            Some("10.0.0.1/32")
+        );
+        let prefix = IpNet::from_attribute("2001:db8::1/64").unwrap();
+        assert_eq!(prefix.trunc().to_string(), "2001:db8::/64");
+        assert_eq!(
+            IpNet::from_attribute("2001:db8::1").unwrap().prefix_len(),
+            128
+        );
+        for invalid in ["10.0.0.1/33", "10.0.0/24", ""] {
+            assert!(IpNet::from_attribute(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_date_time() {
+        let expected = RosDateTime {
+            year: 2024,
+            month: 1,
+            day: 2,
+            hour: 10,
+            minute: 0,
+            second: 5,
+        };
+        assert_eq!("jan/02/2024 10:00:05".parse(), Ok(expected));
+        assert_eq!("Jan/02/2024 10:00:05".parse(), Ok(expected));
+        assert_eq!("2024-01-02 10:00:05".parse(), Ok(expected));
+        assert_eq!(expected.to_string(), "2024-01-02 10:00:05");
+        assert!("feb/29/2024".parse::<RosDateTime>().is_ok());
+        assert!("2000-02-29".parse::<RosDateTime>().is_ok());
+        assert_eq!(
+            "dec/31/2023".parse(),
+            Ok(RosDateTime {
+                year: 2023,
+                month: 12,
+                day: 31,
+                ..Default::default()
+            })
+        );
+        for invalid in [
+            "foo/02/2024 10:00:00",
+            "2024-13-02 10:00:00",
+            "2024-01-02 24:00:00",
+            "2024-01-02 10:00",
+            "2024-01-00",
+            "feb/31/2024",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+        ] {
+            assert!(invalid.parse::<RosDateTime>().is_err(), "{invalid}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use crate::{de::from_sentence, ser::to_attributes};
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Values {
+            timeout: RosDuration,
+            limit: RosBytes,
+            interfaces: RosList<String>,
+            mac: MacAddress,
+            address: IpNet,
+            since: RosDateTime,
+            enabled: RosBool,
+        }
+        let values: Values = from_sentence(&[
+            (b"timeout", Some(b"1d00:00:30")),
+            (b"limit", Some(b"10M")),
+            (b"interfaces", Some(b"ether1,ether2")),
+            (b"mac", Some(b"00:11:22:33:44:55")),
+            (b"address", Some(b"10.0.0.1/8")),
+            (b"since", Some(b"feb/29/2024 12:30:00")),
+            (b"enabled", Some(b"yes")),
+        ])
+        .unwrap();
+        assert_eq!(values.timeout.0, Duration::from_secs(24 * 3600 + 30));
+        let attributes = to_attributes(&values).unwrap();
+        let attributes: Vec<_> = attributes
+            .iter()
+            .map(|(key, value)| (key.as_str(), std::str::from_utf8(value).unwrap()))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                ("timeout", "1d30s"),
+                ("limit", "10000000"),
+                ("interfaces", "ether1,ether2"),
+                ("mac", "00:11:22:33:44:55"),
+                ("address", "10.0.0.1/8"),
+                ("since", "2024-02-29 12:30:00"),
+                ("enabled", "yes"),
+            ]
+        );
+    }
+}